@@ -0,0 +1,69 @@
+/*
+ * Example native plugin. Build it with
+ *
+ *     gcc -shared -fPIC -I ms-runtime/include examples/plugin/math.c -o libmath.so
+ *
+ * and load it from a script with `extern "./libmath.so" { def add(a: int, b: int) -> int; }`
+ */
+
+#include <string.h>
+
+#include "mintscript.h"
+
+static const MsApi *ms;
+
+static const MsValue *add(MsCall *call) {
+    const MsValue *a = ms->arg(call, 0);
+    const MsValue *b = ms->arg(call, 1);
+
+    if (ms->arg_count(call) != 2 || ms->type_of(a) != MS_INT || ms->type_of(b) != MS_INT) {
+        ms->raise(call, "add expects two ints");
+        return NULL;
+    }
+
+    return ms->new_int(call, ms->as_int(a) + ms->as_int(b));
+}
+
+static const MsValue *greet(MsCall *call) {
+    size_t length = 0;
+    const char *name = ms->as_string(ms->arg(call, 0), &length);
+
+    if (name == NULL) {
+        ms->raise(call, "greet expects a string");
+        return NULL;
+    }
+
+    char buffer[256] = "Hello, ";
+    size_t prefix = strlen(buffer);
+
+    if (length > sizeof(buffer) - prefix) {
+        length = sizeof(buffer) - prefix;
+    }
+
+    memcpy(buffer + prefix, name, length);
+
+    return ms->new_string(call, buffer, prefix + length);
+}
+
+/* Returns its argument as is, objects are passed through by handle */
+static const MsValue *identity(MsCall *call) {
+    return ms->arg(call, 0);
+}
+
+MS_EXPORT uint32_t ms_plugin_abi_version(void) {
+    return MS_ABI_VERSION;
+}
+
+MS_EXPORT int32_t ms_plugin_init(const MsApi *api, MsRegistry *registry) {
+    if (api->version != MS_ABI_VERSION) {
+        return 1;
+    }
+
+    ms = api;
+
+    api->register_fn(registry, "add", add);
+    api->register_fn(registry, "greet", greet);
+    api->register_fn(registry, "identity", identity);
+
+    return 0;
+}
@@ -0,0 +1,77 @@
+/*
+ * Native `std.mod` module used by the examples. Build it at the root of the repository with
+ *
+ *     gcc -shared -fPIC -I ms-runtime/include examples/plugin/std.c -o libstd.mod.so
+ *
+ * `ms run` run from there finds `libstd.mod.so` in the current directory. Use the platform's
+ * name elsewhere, `libstd.mod.dylib` on macOS or `std.mod.dll` on Windows.
+ */
+
+#include <inttypes.h>
+#include <stdio.h>
+
+#include "mintscript.h"
+
+static const MsApi *ms;
+
+static void print_value(const MsValue *value) {
+    size_t length = 0;
+    const char *string;
+
+    switch (ms->type_of(value)) {
+    case MS_BOOL:
+        printf("%s", ms->as_bool(value) ? "true" : "false");
+        break;
+    case MS_INT:
+    case MS_LONG:
+        printf("%" PRId64, ms->as_long(value));
+        break;
+    case MS_FLOAT:
+    case MS_DOUBLE:
+        printf("%g", ms->as_double(value));
+        break;
+    case MS_STRING:
+        string = ms->as_string(value, &length);
+        fwrite(string, 1, length, stdout);
+        break;
+    case MS_OBJECT:
+        printf("<object>");
+        break;
+    default:
+        printf("null");
+        break;
+    }
+}
+
+/* Prints its arguments separated by spaces */
+static const MsValue *print(MsCall *call) {
+    size_t count = ms->arg_count(call);
+
+    for (size_t i = 0; i < count; i++) {
+        if (i > 0) {
+            putchar(' ');
+        }
+
+        print_value(ms->arg(call, i));
+    }
+
+    putchar('\n');
+
+    return NULL;
+}
+
+MS_EXPORT uint32_t ms_plugin_abi_version(void) {
+    return MS_ABI_VERSION;
+}
+
+MS_EXPORT int32_t ms_plugin_init(const MsApi *api, MsRegistry *registry) {
+    if (api->version != MS_ABI_VERSION) {
+        return 1;
+    }
+
+    ms = api;
+
+    api->register_fn(registry, "print", print);
+
+    return 0;
+}
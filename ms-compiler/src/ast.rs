@@ -0,0 +1,250 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Function(Function),
+    Extern(Extern),
+    Struct(Struct),
+}
+
+// A type annotation as written in the source, e.g. `int`, `Foo` or `def(int) -> int`
+#[derive(Debug, Clone)]
+pub struct TypeName {
+    pub name: String,
+    // Parameter and return types of a function type, whose name is `def`
+    pub signature: Option<Box<FunctionType>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionType {
+    pub params: Vec<TypeName>,
+    pub ret: Option<TypeName>,
+}
+
+// Name of a function type as used in diagnostics, `def(int, int) -> int`, without
+// the arrow for functions that don't return a value
+pub fn function_type_name(params: &[String], ret: Option<&str>) -> String {
+    match ret {
+        Some(ret) => format!("def({}) -> {}", params.join(", "), ret),
+        None => format!("def({})", params.join(", ")),
+    }
+}
+
+impl Display for TypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(signature) = &self.signature else {
+            return write!(f, "{}", self.name);
+        };
+
+        let params: Vec<String> = signature.params.iter().map(|ty| ty.to_string()).collect();
+        let ret = signature.ret.as_ref().map(|ty| ty.to_string());
+
+        write!(f, "{}", function_type_name(&params, ret.as_deref()))
+    }
+}
+
+// A parameter named `self` without a type annotation receives the struct value
+// the method was called on and is always the first parameter
+pub const SELF_PARAM: &str = "self";
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: TypeName,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    // Struct the function belongs to, for `def Foo.new(...)` and functions declared in a struct
+    pub owner: Option<String>,
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: Option<TypeName>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+// struct Foo { boo: int }
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub functions: Vec<Function>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: TypeName,
+    pub span: Span,
+}
+
+// extern "std.mod" { def print(...); }
+#[derive(Debug, Clone)]
+pub struct Extern {
+    pub module: String,
+    pub functions: Vec<ExternFunction>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternFunction {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub variadic: bool,
+    pub ret: Option<TypeName>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Let {
+        name: String,
+        ty: Option<TypeName>,
+        value: Expr,
+        span: Span,
+    },
+    Assign {
+        target: Expr,
+        value: Expr,
+        span: Span,
+    },
+    Expr(Expr),
+    If {
+        condition: Expr,
+        then_block: Vec<Stmt>,
+        else_block: Vec<Stmt>,
+        span: Span,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    Return {
+        value: Option<Expr>,
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expr(expr) => expr.span,
+            Stmt::Let { span, .. }
+            | Stmt::Assign { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::Break { span }
+            | Stmt::Continue { span } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    // Short-circuiting, the right hand side is only evaluated when needed
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Integer(i32),
+    Float(f32),
+    String(String),
+    Boolean(bool),
+    Identifier(String),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Call {
+        callee: String,
+        args: Vec<Expr>,
+    },
+    // obj.method(args) or Foo.function(args)
+    MethodCall {
+        object: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    // obj.field
+    Field {
+        object: Box<Expr>,
+        field: String,
+    },
+    // Foo { boo: value }
+    StructLiteral {
+        name: String,
+        fields: Vec<FieldInit>,
+    },
+    // def(x: int) -> int { return x + y; }, captures the variables it uses by value
+    Lambda {
+        params: Vec<Param>,
+        ret: Option<TypeName>,
+        body: Vec<Stmt>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
@@ -0,0 +1,954 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::ast::{
+    function_type_name, BinaryOp, Expr, ExprKind, FieldInit, Function, Item, Param, Program, Span,
+    Stmt, TypeName, UnaryOp,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    String,
+    Bool,
+    Void,
+    Struct(String),
+    // Function values, a `ret` of `Void` for functions that don't return a value
+    Function { params: Vec<Type>, ret: Box<Type> },
+    // Result of an expression that already reported an error, compatible with every type
+    // so a single mistake doesn't cascade into more diagnostics
+    Unknown,
+}
+
+impl Type {
+    fn matches(&self, other: &Type) -> bool {
+        *self == Type::Unknown || *other == Type::Unknown || self == other
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Void => write!(f, "void"),
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::Function { params, ret } => {
+                let params: Vec<String> = params.iter().map(|ty| ty.to_string()).collect();
+                let ret = match **ret {
+                    Type::Void => None,
+                    ref ty => Some(ty.to_string()),
+                };
+
+                write!(f, "{}", function_type_name(&params, ret.as_deref()))
+            }
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+struct Signature {
+    params: Vec<Type>,
+    variadic: bool,
+    ret: Type,
+    receiver: bool,
+}
+
+struct Context {
+    scopes: Vec<HashMap<String, Type>>,
+    // Variables of the enclosing functions a lambda can capture
+    captures: HashMap<String, Type>,
+    ret: Type,
+    loops: usize,
+}
+
+impl Context {
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.captures.get(name))
+    }
+
+    fn is_captured(&self, name: &str) -> bool {
+        !self.scopes.iter().any(|scope| scope.contains_key(name))
+            && self.captures.contains_key(name)
+    }
+
+    // Every variable in scope, for the lambdas declared at this point
+    fn visible(&self) -> HashMap<String, Type> {
+        let mut visible = self.captures.clone();
+
+        for scope in self.scopes.iter() {
+            visible.extend(scope.iter().map(|(name, ty)| (name.clone(), ty.clone())));
+        }
+
+        visible
+    }
+
+    fn declare(&mut self, name: &str, ty: Type) {
+        self.scopes
+            .last_mut()
+            .expect("Function scope is always present")
+            .insert(name.to_string(), ty);
+    }
+}
+
+// Types the checker resolved, code generation reads them instead of inferring its own.
+// Expressions are keyed by address, the program must not move between the two passes
+#[derive(Default)]
+pub(crate) struct Types {
+    exprs: HashMap<*const Expr, Type>,
+}
+
+impl Types {
+    pub(crate) fn of(&self, expr: &Expr) -> &Type {
+        self.exprs
+            .get(&(expr as *const Expr))
+            .expect("Expression was not type checked")
+    }
+}
+
+pub(crate) struct Checker {
+    functions: HashMap<String, Signature>,
+    structs: HashMap<String, Vec<(String, Type)>>,
+    types: Types,
+    errors: Vec<String>,
+}
+
+impl Checker {
+    pub(crate) fn new() -> Checker {
+        Checker {
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            types: Types::default(),
+            errors: vec![],
+        }
+    }
+
+    // Check the whole program, returns the type of every expression or every diagnostic
+    // found, one per line
+    pub(crate) fn check(mut self, program: &Program) -> Result<Types, String> {
+        self.declare_items(program);
+
+        for item in program.items.iter() {
+            match item {
+                Item::Function(function) => self.check_function(function),
+                Item::Struct(declaration) => {
+                    for function in declaration.functions.iter() {
+                        self.check_function(function);
+                    }
+                }
+                Item::Extern(_) => {}
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.types)
+        } else {
+            Err(self.errors.join("\n"))
+        }
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(format!("{}: {}", span, message));
+    }
+
+    fn declare_items(&mut self, program: &Program) {
+        // Struct names first so field and parameter types can refer to any of them
+        for item in program.items.iter() {
+            if let Item::Struct(declaration) = item {
+                if self.structs.contains_key(&declaration.name) {
+                    self.error(
+                        declaration.span,
+                        format!("Struct '{}' is already defined", declaration.name),
+                    );
+                }
+
+                self.structs.insert(declaration.name.clone(), vec![]);
+            }
+        }
+
+        for item in program.items.iter() {
+            match item {
+                Item::Struct(declaration) => {
+                    let mut fields: Vec<(String, Type)> = vec![];
+
+                    for field in declaration.fields.iter() {
+                        if fields.iter().any(|(name, _)| *name == field.name) {
+                            self.error(
+                                field.span,
+                                format!(
+                                    "Duplicate field '{}' in struct '{}'",
+                                    field.name, declaration.name
+                                ),
+                            );
+                        }
+
+                        let ty = self.resolve(&field.ty);
+                        fields.push((field.name.clone(), ty));
+                    }
+
+                    self.structs.insert(declaration.name.clone(), fields);
+
+                    for function in declaration.functions.iter() {
+                        self.declare_function(function);
+                    }
+                }
+                Item::Function(function) => self.declare_function(function),
+                Item::Extern(ext) => {
+                    for function in ext.functions.iter() {
+                        let signature = Signature {
+                            params: function
+                                .params
+                                .iter()
+                                .map(|p| self.resolve(&p.ty))
+                                .collect(),
+                            variadic: function.variadic,
+                            ret: self.resolve_return(&function.ret),
+                            receiver: false,
+                        };
+
+                        self.declare(function.name.clone(), function.span, signature);
+                    }
+                }
+            }
+        }
+    }
+
+    fn declare_function(&mut self, function: &Function) {
+        let name = match &function.owner {
+            Some(owner) => {
+                if !self.structs.contains_key(owner) {
+                    self.error(function.span, format!("Unknown struct '{}'", owner));
+                }
+
+                format!("{}.{}", owner, function.name)
+            }
+            None => function.name.clone(),
+        };
+
+        let signature = Signature {
+            params: function
+                .params
+                .iter()
+                .map(|p| self.resolve(&p.ty))
+                .collect(),
+            variadic: false,
+            ret: self.resolve_return(&function.ret),
+            receiver: function.owner.is_some()
+                && function
+                    .params
+                    .first()
+                    .is_some_and(|param| param.name == crate::ast::SELF_PARAM),
+        };
+
+        self.declare(name, function.span, signature);
+    }
+
+    fn declare(&mut self, name: String, span: Span, signature: Signature) {
+        if self.functions.contains_key(&name) {
+            self.error(span, format!("Function '{}' is already defined", name));
+        }
+
+        self.functions.insert(name, signature);
+    }
+
+    fn resolve(&mut self, ty: &TypeName) -> Type {
+        if let Some(signature) = &ty.signature {
+            return Type::Function {
+                params: signature.params.iter().map(|p| self.resolve(p)).collect(),
+                ret: Box::new(self.resolve_return(&signature.ret)),
+            };
+        }
+
+        match ty.name.as_str() {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "string" => Type::String,
+            "bool" => Type::Bool,
+            name if self.structs.contains_key(name) => Type::Struct(name.to_string()),
+            name => {
+                self.error(ty.span, format!("Unknown type '{}'", name));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn resolve_return(&mut self, ty: &Option<TypeName>) -> Type {
+        match ty {
+            Some(ty) => self.resolve(ty),
+            None => Type::Void,
+        }
+    }
+
+    fn check_function(&mut self, function: &Function) {
+        self.check_body(
+            &function.name,
+            &function.params,
+            &function.ret,
+            &function.body,
+            HashMap::new(),
+            function.span,
+        );
+    }
+
+    // Check the body of a function or lambda, returns the type of the function
+    fn check_body(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        ret: &Option<TypeName>,
+        body: &[Stmt],
+        captures: HashMap<String, Type>,
+        span: Span,
+    ) -> Type {
+        let mut context = Context {
+            scopes: vec![HashMap::new()],
+            captures,
+            ret: self.resolve_return(ret),
+            loops: 0,
+        };
+
+        for param in params.iter() {
+            if context.scopes[0].contains_key(&param.name) {
+                self.error(param.span, format!("Duplicate parameter '{}'", param.name));
+            }
+
+            let ty = self.resolve(&param.ty);
+            context.declare(&param.name, ty);
+        }
+
+        self.check_block(body, &mut context);
+
+        if context.ret != Type::Void && !always_returns(body) {
+            self.error(
+                span,
+                format!(
+                    "Function '{}' must return a value of type '{}' on every path",
+                    name, context.ret
+                ),
+            );
+        }
+
+        Type::Function {
+            params: params
+                .iter()
+                .map(|param| context.scopes[0][&param.name].clone())
+                .collect(),
+            ret: Box::new(context.ret),
+        }
+    }
+
+    fn check_block(&mut self, stmts: &[Stmt], context: &mut Context) {
+        context.scopes.push(HashMap::new());
+
+        for stmt in stmts.iter() {
+            self.check_stmt(stmt, context);
+        }
+
+        context.scopes.pop();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt, context: &mut Context) {
+        match stmt {
+            Stmt::Let {
+                name, ty, value, ..
+            } => {
+                let value_ty = self.check_value(value, context);
+
+                let ty = match ty {
+                    Some(ty) => {
+                        let declared = self.resolve(ty);
+                        self.expect_type(&declared, &value_ty, value.span);
+                        declared
+                    }
+                    None => value_ty,
+                };
+
+                context.declare(name, ty);
+            }
+            Stmt::Assign { target, value, .. } => {
+                if let ExprKind::Identifier(name) = &target.kind {
+                    if context.is_captured(name) {
+                        self.error(
+                            target.span,
+                            format!("Cannot assign to captured variable '{}'", name),
+                        );
+                    }
+                }
+
+                let target_ty = match &target.kind {
+                    ExprKind::Identifier(_) | ExprKind::Field { .. } => {
+                        self.check_expr(target, context)
+                    }
+                    _ => {
+                        self.error(target.span, "Invalid assignment target".to_string());
+                        Type::Unknown
+                    }
+                };
+
+                let value_ty = self.check_value(value, context);
+                self.expect_type(&target_ty, &value_ty, value.span);
+            }
+            Stmt::Expr(expr) => {
+                self.check_expr(expr, context);
+            }
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+                ..
+            } => {
+                let ty = self.check_value(condition, context);
+                self.expect_type(&Type::Bool, &ty, condition.span);
+
+                self.check_block(then_block, context);
+                self.check_block(else_block, context);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let ty = self.check_value(condition, context);
+                self.expect_type(&Type::Bool, &ty, condition.span);
+
+                context.loops += 1;
+                self.check_block(body, context);
+                context.loops -= 1;
+            }
+            Stmt::Return { value, span } => match value {
+                Some(value) => {
+                    let ty = self.check_value(value, context);
+
+                    if context.ret == Type::Void {
+                        self.error(
+                            *span,
+                            "Cannot return a value from a function without a return type"
+                                .to_string(),
+                        );
+                    } else {
+                        let ret = context.ret.clone();
+                        self.expect_type(&ret, &ty, value.span);
+                    }
+                }
+                None => {
+                    if context.ret != Type::Void {
+                        self.error(
+                            *span,
+                            format!("Expected a return value of type '{}'", context.ret),
+                        );
+                    }
+                }
+            },
+            Stmt::Break { span } => {
+                if context.loops == 0 {
+                    self.error(*span, "'break' outside of a loop".to_string());
+                }
+            }
+            Stmt::Continue { span } => {
+                if context.loops == 0 {
+                    self.error(*span, "'continue' outside of a loop".to_string());
+                }
+            }
+        }
+    }
+
+    fn expect_type(&mut self, expected: &Type, found: &Type, span: Span) {
+        if !expected.matches(found) {
+            self.error(
+                span,
+                format!("Expected a value of type '{}', found '{}'", expected, found),
+            );
+        }
+    }
+
+    // Check an expression used as a value, which excludes calls to functions without a return type
+    fn check_value(&mut self, expr: &Expr, context: &mut Context) -> Type {
+        let ty = self.check_expr(expr, context);
+
+        if ty == Type::Void {
+            self.error(expr.span, "Expression does not produce a value".to_string());
+            return Type::Unknown;
+        }
+
+        ty
+    }
+
+    fn check_expr(&mut self, expr: &Expr, context: &mut Context) -> Type {
+        let ty = self.infer_expr(expr, context);
+
+        self.types.exprs.insert(expr as *const Expr, ty.clone());
+
+        ty
+    }
+
+    fn infer_expr(&mut self, expr: &Expr, context: &mut Context) -> Type {
+        match &expr.kind {
+            ExprKind::Integer(_) => Type::Int,
+            ExprKind::Float(_) => Type::Float,
+            ExprKind::String(_) => Type::String,
+            ExprKind::Boolean(_) => Type::Bool,
+            ExprKind::Identifier(name) => {
+                if let Some(ty) = context.lookup(name) {
+                    return ty.clone();
+                }
+
+                // A function used as a value
+                match self.functions.get(name) {
+                    Some(signature) if signature.variadic => {
+                        self.error(
+                            expr.span,
+                            format!("Variadic function '{}' cannot be used as a value", name),
+                        );
+                        Type::Unknown
+                    }
+                    Some(signature) => Type::Function {
+                        params: signature.params.clone(),
+                        ret: Box::new(signature.ret.clone()),
+                    },
+                    None => {
+                        self.error(expr.span, format!("Unknown variable '{}'", name));
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs_ty = self.check_value(lhs, context);
+                let rhs_ty = self.check_value(rhs, context);
+
+                self.check_binary(*op, lhs_ty, rhs_ty, expr.span)
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } => {
+                let ty = self.check_value(inner, context);
+
+                match ty {
+                    Type::Int | Type::Float | Type::Unknown => ty,
+                    _ => {
+                        self.error(
+                            expr.span,
+                            format!("Operator '-' cannot be applied to '{}'", ty),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Not,
+                expr: inner,
+            } => {
+                let ty = self.check_value(inner, context);
+
+                if !ty.matches(&Type::Bool) {
+                    self.error(
+                        expr.span,
+                        format!("Operator '!' cannot be applied to '{}'", ty),
+                    );
+                }
+
+                Type::Bool
+            }
+            ExprKind::Call { callee, args } => {
+                self.check_call(callee, None, args, expr.span, context)
+            }
+            ExprKind::MethodCall {
+                object,
+                method,
+                args,
+            } => {
+                // `Foo.new(...)` names a struct unless a local shadows it
+                if let ExprKind::Identifier(name) = &object.kind {
+                    if context.lookup(name).is_none() && self.structs.contains_key(name) {
+                        let callee = format!("{}.{}", name, method);
+
+                        return self.check_call(&callee, None, args, expr.span, context);
+                    }
+                }
+
+                let object_ty = self.check_value(object, context);
+
+                let name = match &object_ty {
+                    Type::Unknown => return Type::Unknown,
+                    Type::Struct(name) => name,
+                    ty => {
+                        self.error(expr.span, format!("Type '{}' has no methods", ty));
+                        return Type::Unknown;
+                    }
+                };
+
+                let callee = format!("{}.{}", name, method);
+
+                match self.functions.get(&callee) {
+                    Some(signature) if signature.receiver => {
+                        self.check_call(&callee, Some(object_ty), args, expr.span, context)
+                    }
+                    Some(_) => {
+                        self.error(
+                            expr.span,
+                            format!("'{}' is not a method, call it as {}(...)", callee, callee),
+                        );
+                        Type::Unknown
+                    }
+                    None => {
+                        self.error(
+                            expr.span,
+                            format!("Struct '{}' has no function '{}'", name, method),
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Field { object, field } => {
+                let object_ty = self.check_value(object, context);
+                self.field_type(&object_ty, field, expr.span)
+            }
+            ExprKind::StructLiteral { name, fields } => {
+                self.check_struct_literal(name, fields, expr.span, context)
+            }
+            ExprKind::Lambda { params, ret, body } => {
+                let captures = context.visible();
+
+                self.check_body("<lambda>", params, ret, body, captures, expr.span)
+            }
+        }
+    }
+
+    fn check_binary(&mut self, op: BinaryOp, lhs: Type, rhs: Type, span: Span) -> Type {
+        if lhs == Type::Unknown || rhs == Type::Unknown {
+            return match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                    Type::Unknown
+                }
+                _ => Type::Bool,
+            };
+        }
+
+        let result = match (op, &lhs, &rhs) {
+            (BinaryOp::And | BinaryOp::Or, Type::Bool, Type::Bool) => Some(Type::Bool),
+            (BinaryOp::Add, Type::String, Type::String) => Some(Type::String),
+            (
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem,
+                Type::Int | Type::Float,
+                _,
+            ) if lhs == rhs => Some(lhs.clone()),
+            (
+                BinaryOp::Eq | BinaryOp::Ne,
+                Type::Int | Type::Float | Type::String | Type::Bool,
+                _,
+            ) if lhs == rhs => Some(Type::Bool),
+            (
+                BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge,
+                Type::Int | Type::Float,
+                _,
+            ) if lhs == rhs => Some(Type::Bool),
+            _ => None,
+        };
+
+        match result {
+            Some(ty) => ty,
+            None => {
+                self.error(
+                    span,
+                    format!(
+                        "Operator '{}' cannot be applied to '{}' and '{}'",
+                        operator(op),
+                        lhs,
+                        rhs
+                    ),
+                );
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        callee: &str,
+        receiver: Option<Type>,
+        args: &[Expr],
+        span: Span,
+        context: &mut Context,
+    ) -> Type {
+        let arg_types: Vec<Type> = args
+            .iter()
+            .map(|arg| self.check_value(arg, context))
+            .collect();
+
+        // Variables shadow functions, calling one calls the function value it holds
+        let local = match receiver {
+            Some(_) => None,
+            None => context.lookup(callee).cloned(),
+        };
+
+        let (params, variadic, ret) = match local {
+            Some(Type::Function { params, ret }) => (params, false, *ret),
+            Some(Type::Unknown) => return Type::Unknown,
+            Some(ty) => {
+                self.error(span, format!("Cannot call '{}' of type '{}'", callee, ty));
+                return Type::Unknown;
+            }
+            None => {
+                let Some(signature) = self.functions.get(callee) else {
+                    self.error(span, format!("Unknown function '{}'", callee));
+                    return Type::Unknown;
+                };
+
+                // The receiver was already checked against the first parameter
+                (
+                    signature.params[receiver.iter().count()..].to_vec(),
+                    signature.variadic,
+                    signature.ret.clone(),
+                )
+            }
+        };
+
+        if args.len() < params.len() || (!variadic && args.len() > params.len()) {
+            let message = format!(
+                "Function '{}' expects {} argument(s), found {}",
+                callee,
+                params.len(),
+                args.len()
+            );
+
+            self.error(span, message);
+            return ret;
+        }
+
+        let mismatches: Vec<(Span, String)> = params
+            .iter()
+            .zip(arg_types.iter())
+            .zip(args.iter())
+            .filter(|((param, ty), _)| !param.matches(ty))
+            .map(|((param, ty), arg)| {
+                (
+                    arg.span,
+                    format!("Expected an argument of type '{}', found '{}'", param, ty),
+                )
+            })
+            .collect();
+
+        for (span, message) in mismatches {
+            self.error(span, message);
+        }
+
+        ret
+    }
+
+    fn field_type(&mut self, object: &Type, field: &str, span: Span) -> Type {
+        let name = match object {
+            Type::Unknown => return Type::Unknown,
+            Type::Struct(name) => name,
+            ty => {
+                self.error(span, format!("Type '{}' has no fields", ty));
+                return Type::Unknown;
+            }
+        };
+
+        let found = self.structs.get(name).and_then(|fields| {
+            fields
+                .iter()
+                .find(|(field_name, _)| field_name == field)
+                .map(|(_, ty)| ty.clone())
+        });
+
+        match found {
+            Some(ty) => ty,
+            None => {
+                self.error(span, format!("Struct '{}' has no field '{}'", name, field));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[FieldInit],
+        span: Span,
+        context: &mut Context,
+    ) -> Type {
+        let Some(declared) = self.structs.get(name).cloned() else {
+            self.error(span, format!("Unknown struct '{}'", name));
+
+            for init in fields.iter() {
+                self.check_value(&init.value, context);
+            }
+
+            return Type::Unknown;
+        };
+
+        for (index, init) in fields.iter().enumerate() {
+            let ty = self.check_value(&init.value, context);
+
+            if fields[..index].iter().any(|other| other.name == init.name) {
+                self.error(
+                    init.span,
+                    format!("Field '{}' is initialized more than once", init.name),
+                );
+            }
+
+            match declared.iter().find(|(field, _)| *field == init.name) {
+                Some((_, expected)) => self.expect_type(expected, &ty, init.value.span),
+                None => self.error(
+                    init.span,
+                    format!("Struct '{}' has no field '{}'", name, init.name),
+                ),
+            }
+        }
+
+        for (field, _) in declared.iter() {
+            if !fields.iter().any(|init| init.name == *field) {
+                self.error(
+                    span,
+                    format!("Missing field '{}' in '{}' literal", field, name),
+                );
+            }
+        }
+
+        Type::Struct(name.to_string())
+    }
+}
+
+fn operator(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+    }
+}
+
+// Whether a block always ends in a `return`, either directly, through both branches
+// of an `if` or through a `while true` loop that never breaks out
+fn always_returns(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::If {
+            then_block,
+            else_block,
+            ..
+        } => always_returns(then_block) && always_returns(else_block),
+        Stmt::While {
+            condition, body, ..
+        } => matches!(condition.kind, ExprKind::Boolean(true)) && !breaks(body),
+        _ => false,
+    })
+}
+
+// Whether a loop body contains a `break` for that loop
+fn breaks(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Break { .. } => true,
+        Stmt::If {
+            then_block,
+            else_block,
+            ..
+        } => breaks(then_block) || breaks(else_block),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Result<(), String> {
+        Checker::new()
+            .check(&crate::parse(source).unwrap())
+            .map(|_| ())
+    }
+
+    #[test]
+    fn checker_accepts_valid_program() {
+        let source = "
+            extern \"std.mod\" { def print(...); }
+
+            struct Foo {
+                boo: int
+                def get(self) -> int { return self.boo; }
+            }
+
+            def Foo.new(value: int) -> Foo {
+                return Foo { boo: value };
+            }
+
+            def main() {
+                let foo: Foo = Foo.new(1);
+                let total = foo.get() + foo.boo;
+                let ratio = 1.5 / 2.0;
+                if total > 1 { print(\"total\", total, ratio); }
+            }
+        ";
+
+        assert_eq!(check(source), Ok(()));
+    }
+
+    #[test]
+    fn checker_reports_every_error() {
+        let source = "struct Foo { boo: int }\n\
+                      def f(a: int) -> int { return a; }\n\
+                      def main() {\n\
+                      let x: int = \"a\";\n\
+                      let y = 1 + 2.0;\n\
+                      let foo = Foo { boo: true };\n\
+                      f(1, 2);\n\
+                      if 1 { }\n\
+                      }";
+
+        let errors = check(source).unwrap_err();
+        let errors: Vec<&str> = errors.lines().collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                "4:14: Expected a value of type 'int', found 'string'",
+                "5:11: Operator '+' cannot be applied to 'int' and 'float'",
+                "6:22: Expected a value of type 'int', found 'bool'",
+                "7:1: Function 'f' expects 1 argument(s), found 2",
+                "8:4: Expected a value of type 'bool', found 'int'",
+            ]
+        );
+    }
+
+    #[test]
+    fn checker_return_paths() {
+        assert!(check("def f(a: int) -> int { if a > 0 { return 1; } }")
+            .unwrap_err()
+            .contains("must return a value of type 'int' on every path"));
+        assert_eq!(
+            check("def f(a: int) -> int { if a > 0 { return 1; } else { return 2; } }"),
+            Ok(())
+        );
+        assert_eq!(check("def f() -> int { while true { return 1; } }"), Ok(()));
+        assert!(check("def f() { return 1; }").is_err());
+    }
+
+    #[test]
+    fn checker_unknown_names() {
+        let errors = check("def f(a: Bar) -> int { return b; }").unwrap_err();
+
+        assert!(errors.contains("Unknown type 'Bar'"));
+        assert!(errors.contains("Unknown variable 'b'"));
+    }
+
+    #[test]
+    fn checker_void_call_as_value() {
+        assert!(check("def f() {} def main() { let x = f(); }")
+            .unwrap_err()
+            .contains("Expression does not produce a value"));
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use ms_runtime::{Code, Instruction};
+
+use crate::{
+    ast::{
+        BinaryOp, Expr, ExprKind, FieldInit, Function, Item, Param, Program, Stmt, Struct, UnaryOp,
+        SELF_PARAM,
+    },
+    checker::{Type, Types},
+};
+
+struct Signature {
+    module: String,
+    // Takes the object it is called on as first argument
+    receiver: bool,
+}
+
+#[derive(Clone)]
+struct Local {
+    index: u32,
+    // Captured by the lambda being generated, `index` is then the index of the upvalue
+    captured: bool,
+}
+
+// Local variables of the function being generated, indexed by name per block scope
+struct Locals {
+    scopes: Vec<HashMap<String, Local>>,
+    count: u32,
+    // Qualified name of the function, lambdas declared in it are named after it
+    name: String,
+    // Variables of the enclosing functions when generating a lambda, and the ones it
+    // captured in upvalue order
+    outer: HashMap<String, Local>,
+    captures: Vec<String>,
+    // Functions generated for the lambdas declared in the function
+    lambdas: Vec<Instruction>,
+}
+
+impl Locals {
+    fn new(name: &str, outer: HashMap<String, Local>) -> Locals {
+        Locals {
+            scopes: vec![HashMap::new()],
+            count: 0,
+            name: name.to_string(),
+            outer,
+            captures: vec![],
+            lambdas: vec![],
+        }
+    }
+
+    // Whether a variable is in scope. Use `resolve` to generate code accessing it
+    fn contains(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name)) || self.outer.contains_key(name)
+    }
+
+    // Variable in scope, capturing it when it belongs to an enclosing function
+    fn resolve(&mut self, name: &str) -> Option<Local> {
+        if let Some(local) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Some(local.clone());
+        }
+
+        self.outer.get(name)?;
+
+        let index = match self.captures.iter().position(|capture| capture == name) {
+            Some(index) => index,
+            None => {
+                self.captures.push(name.to_string());
+                self.captures.len() - 1
+            }
+        };
+
+        Some(Local {
+            index: index as u32,
+            captured: true,
+        })
+    }
+
+    // Every variable in scope, for the lambdas declared at this point
+    fn visible(&self) -> HashMap<String, Local> {
+        let mut visible = self.outer.clone();
+
+        for scope in self.scopes.iter() {
+            visible.extend(
+                scope
+                    .iter()
+                    .map(|(name, local)| (name.clone(), local.clone())),
+            );
+        }
+
+        visible
+    }
+
+    fn declare(&mut self, name: &str) -> u32 {
+        let index = self.count;
+
+        self.count += 1;
+        self.scopes
+            .last_mut()
+            .expect("Function scope is always present")
+            .insert(
+                name.to_string(),
+                Local {
+                    index,
+                    captured: false,
+                },
+            );
+
+        index
+    }
+}
+
+// Generates the code of a program the checker accepted, so every name, type and
+// argument count is known to be valid here
+pub(crate) struct Generator<'a> {
+    module: String,
+    types: &'a Types,
+    functions: HashMap<String, Signature>,
+    // Field names of each struct, in field index order
+    structs: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Generator<'a> {
+    pub(crate) fn new(module: &str, types: &'a Types) -> Generator<'a> {
+        Generator {
+            module: module.to_string(),
+            types,
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn generate(&mut self, program: &Program) -> Code {
+        let mut code = vec![];
+
+        // Structs first, functions of a struct may be declared before it
+        for item in program.items.iter() {
+            if let Item::Struct(declaration) = item {
+                self.declare_struct(declaration);
+            }
+        }
+
+        let functions: Vec<&Function> = program
+            .items
+            .iter()
+            .flat_map(|item| match item {
+                Item::Function(function) => vec![function],
+                Item::Struct(declaration) => declaration.functions.iter().collect(),
+                Item::Extern(_) => vec![],
+            })
+            .collect();
+
+        for function in functions.iter() {
+            self.functions.insert(
+                qualified_name(function),
+                Signature {
+                    module: self.module.clone(),
+                    receiver: function.owner.is_some()
+                        && function
+                            .params
+                            .first()
+                            .is_some_and(|param| param.name == SELF_PARAM),
+                },
+            );
+        }
+
+        // Dynamic modules are loaded once per name, so merge every extern block for it
+        let mut externs: Vec<(String, Code)> = vec![];
+
+        for item in program.items.iter() {
+            match item {
+                Item::Function(_) | Item::Struct(_) => {}
+                Item::Extern(ext) => {
+                    let index = match externs.iter().position(|(name, _)| *name == ext.module) {
+                        Some(index) => index,
+                        None => {
+                            externs.push((ext.module.clone(), vec![]));
+                            externs.len() - 1
+                        }
+                    };
+
+                    for function in ext.functions.iter() {
+                        self.functions.insert(
+                            function.name.clone(),
+                            Signature {
+                                module: ext.module.clone(),
+                                receiver: false,
+                            },
+                        );
+
+                        externs[index].1.push(Instruction::GetFunction {
+                            name: function.name.clone(),
+                            alias: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (name, functions) in externs {
+            code.push(Instruction::LoadModule {
+                name,
+                code: functions,
+            });
+        }
+
+        let mut module_code = vec![];
+        let mut struct_code: Vec<(String, Code)> = vec![];
+
+        for function in functions.iter() {
+            // The function followed by the functions of its lambdas
+            let instructions = self.generate_function(function);
+
+            let Some(owner) = &function.owner else {
+                module_code.extend(instructions);
+                continue;
+            };
+
+            match struct_code.iter_mut().find(|(name, _)| name == owner) {
+                Some((_, code)) => code.extend(instructions),
+                None => struct_code.push((owner.clone(), instructions)),
+            }
+        }
+
+        for (name, code) in struct_code {
+            module_code.push(Instruction::Struct { name, code });
+        }
+
+        code.push(Instruction::Module {
+            name: self.module.clone(),
+            code: module_code,
+        });
+
+        code
+    }
+
+    fn declare_struct(&mut self, declaration: &Struct) {
+        let fields = declaration
+            .fields
+            .iter()
+            .map(|field| field.name.clone())
+            .collect();
+
+        self.structs.insert(declaration.name.clone(), fields);
+    }
+
+    // Name of the struct an expression evaluates to
+    fn struct_of(&self, expr: &Expr) -> &str {
+        match self.types.of(expr) {
+            Type::Struct(name) => name,
+            ty => unreachable!("Expected a struct, the checker found '{}'", ty),
+        }
+    }
+
+    // Index of `field` in the struct `name`
+    fn field(&self, name: &str, field: &str) -> u32 {
+        self.structs[name]
+            .iter()
+            .position(|declared| declared == field)
+            .expect("Fields are checked") as u32
+    }
+
+    // Resolve `object.method(...)` to the qualified function name, and whether `object` is
+    // passed as receiver (instance method) or is a struct name (associated function)
+    fn method_target(&self, object: &Expr, method: &str, locals: &Locals) -> (String, bool) {
+        if let ExprKind::Identifier(name) = &object.kind {
+            if !locals.contains(name) && self.structs.contains_key(name) {
+                return (format!("{}.{}", name, method), false);
+            }
+        }
+
+        let name = format!("{}.{}", self.struct_of(object), method);
+        let receiver = self.functions[&name].receiver;
+
+        (name, receiver)
+    }
+
+    fn generate_function(&self, function: &Function) -> Vec<Instruction> {
+        let mut locals = Locals::new(&qualified_name(function), HashMap::new());
+
+        let code = self.generate_body(&function.params, &function.body, &mut locals);
+
+        let mut instructions = vec![Instruction::Fn {
+            name: function.name.clone(),
+            code,
+        }];
+
+        instructions.extend(locals.lambdas);
+
+        instructions
+    }
+
+    fn generate_body(&self, params: &[Param], body: &[Stmt], locals: &mut Locals) -> Code {
+        // Arguments are passed as the first locals of the call frame
+        for param in params.iter() {
+            locals.declare(&param.name);
+        }
+
+        let mut code = vec![];
+
+        self.generate_block(body, locals, &mut code);
+
+        if locals.count as usize > params.len() {
+            code.insert(0, Instruction::ReserveLocal { size: locals.count });
+        }
+
+        code.insert(
+            0,
+            Instruction::Params {
+                count: params.len() as u32,
+            },
+        );
+
+        code
+    }
+
+    // Generate the function of a lambda, declared next to the function it is in, and
+    // push the closure over the variables it captured
+    fn generate_lambda(
+        &self,
+        params: &[Param],
+        body: &[Stmt],
+        locals: &mut Locals,
+        code: &mut Code,
+    ) {
+        let name = format!("{}$lambda{}", locals.name, locals.lambdas.len());
+        let mut lambda = Locals::new(&name, locals.visible());
+
+        let lambda_code = self.generate_body(params, body, &mut lambda);
+
+        for capture in lambda.captures.iter() {
+            self.generate_load(capture, locals, code);
+        }
+
+        code.push(Instruction::FunctionRef {
+            module: self.module.clone(),
+            function: name.clone(),
+            captures: lambda.captures.len() as u32,
+        });
+
+        // Lambdas of struct functions are declared in the struct, `Foo.get$lambda0`
+        let unqualified = match name.split_once('.') {
+            Some((_, name)) => name.to_string(),
+            None => name.clone(),
+        };
+
+        locals.lambdas.push(Instruction::Fn {
+            name: unqualified,
+            code: lambda_code,
+        });
+        locals.lambdas.extend(lambda.lambdas);
+    }
+
+    // Push the value of a variable, or of a function used as a value
+    fn generate_load(&self, name: &str, locals: &mut Locals, code: &mut Code) {
+        if let Some(local) = locals.resolve(name) {
+            code.push(match local.captured {
+                true => Instruction::GetUpvalue { index: local.index },
+                false => Instruction::GetLocal { index: local.index },
+            });
+
+            return;
+        }
+
+        code.push(Instruction::FunctionRef {
+            module: self.functions[name].module.clone(),
+            function: name.to_string(),
+            captures: 0,
+        });
+    }
+
+    fn generate_block(&self, stmts: &[Stmt], locals: &mut Locals, code: &mut Code) {
+        locals.scopes.push(HashMap::new());
+
+        for stmt in stmts.iter() {
+            // Debug info for stack traces
+            code.push(Instruction::Line {
+                line: stmt.span().line as u32,
+            });
+
+            self.generate_stmt(stmt, locals, code);
+        }
+
+        locals.scopes.pop();
+    }
+
+    fn generate_stmt(&self, stmt: &Stmt, locals: &mut Locals, code: &mut Code) {
+        match stmt {
+            Stmt::Let { name, value, .. } => {
+                self.generate_expr(value, locals, code);
+
+                // Declared after the initializer so `let x = x + 1` reads the outer `x`
+                let index = locals.declare(name);
+                code.push(Instruction::SetLocal { index });
+            }
+            Stmt::Assign { target, value, .. } => match &target.kind {
+                ExprKind::Identifier(name) => {
+                    let local = locals.resolve(name).expect("Assignments are checked");
+
+                    self.generate_expr(value, locals, code);
+                    code.push(Instruction::SetLocal { index: local.index });
+                }
+                ExprKind::Field { object, field } => {
+                    let index = self.field(self.struct_of(object), field);
+
+                    // field.set leaves the object on the stack
+                    self.generate_expr(object, locals, code);
+                    self.generate_expr(value, locals, code);
+                    code.push(Instruction::SetField { index });
+                    code.push(Instruction::Pop);
+                }
+                _ => unreachable!("Assignment targets are checked"),
+            },
+            Stmt::Expr(expr) => {
+                self.generate_expr(expr, locals, code);
+
+                if *self.types.of(expr) != Type::Void {
+                    code.push(Instruction::Pop);
+                }
+            }
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+                ..
+            } => {
+                self.generate_expr(condition, locals, code);
+
+                let mut then_code = vec![];
+                let mut else_code = vec![];
+
+                self.generate_block(then_block, locals, &mut then_code);
+                self.generate_block(else_block, locals, &mut else_code);
+
+                code.push(Instruction::Then {
+                    then_block: then_code,
+                    else_block: else_code,
+                });
+            }
+            Stmt::While {
+                condition,
+                body,
+                span,
+            } => {
+                // loop { if !condition { break } body }
+                let mut block = vec![Instruction::Line {
+                    line: span.line as u32,
+                }];
+
+                self.generate_expr(condition, locals, &mut block);
+                block.push(Instruction::Then {
+                    then_block: vec![],
+                    else_block: vec![Instruction::Break],
+                });
+
+                self.generate_block(body, locals, &mut block);
+
+                code.push(Instruction::Loop { block });
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.generate_expr(value, locals, code);
+                }
+
+                code.push(Instruction::Return);
+            }
+            Stmt::Break { .. } => code.push(Instruction::Break),
+            Stmt::Continue { .. } => code.push(Instruction::Continue),
+        }
+    }
+
+    fn generate_expr(&self, expr: &Expr, locals: &mut Locals, code: &mut Code) {
+        match &expr.kind {
+            ExprKind::Integer(value) => code.push(Instruction::PushConstInteger { value: *value }),
+            ExprKind::Float(value) => code.push(Instruction::PushConstFloat { value: *value }),
+            ExprKind::String(value) => code.push(Instruction::PushConstString {
+                value: value.clone(),
+            }),
+            ExprKind::Boolean(value) => code.push(Instruction::PushConstBoolean { value: *value }),
+            ExprKind::Identifier(name) => self.generate_load(name, locals, code),
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                self.generate_expr(lhs, locals, code);
+
+                let mut rhs_code = vec![];
+                self.generate_expr(rhs, locals, &mut rhs_code);
+
+                // The result of the left hand side decides without evaluating the right one
+                let (then_block, else_block) = match op {
+                    BinaryOp::And => (
+                        rhs_code,
+                        vec![Instruction::PushConstBoolean { value: false }],
+                    ),
+                    _ => (
+                        vec![Instruction::PushConstBoolean { value: true }],
+                        rhs_code,
+                    ),
+                };
+
+                code.push(Instruction::Then {
+                    then_block,
+                    else_block,
+                });
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                self.generate_expr(lhs, locals, code);
+                self.generate_expr(rhs, locals, code);
+
+                code.push(match op {
+                    BinaryOp::Add => Instruction::Add,
+                    BinaryOp::Sub => Instruction::Sub,
+                    BinaryOp::Mul => Instruction::Mul,
+                    BinaryOp::Div => Instruction::Div,
+                    BinaryOp::Rem => Instruction::Rem,
+                    BinaryOp::Eq => Instruction::Eq,
+                    BinaryOp::Ne => Instruction::Ne,
+                    BinaryOp::Lt => Instruction::Lt,
+                    BinaryOp::Le => Instruction::Le,
+                    BinaryOp::Gt => Instruction::Gt,
+                    BinaryOp::Ge => Instruction::Ge,
+                    BinaryOp::And | BinaryOp::Or => unreachable!("Lowered to a (then) block above"),
+                });
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } => match inner.kind {
+                ExprKind::Integer(value) => code.push(Instruction::PushConstInteger {
+                    value: value.wrapping_neg(),
+                }),
+                ExprKind::Float(value) => code.push(Instruction::PushConstFloat { value: -value }),
+                _ => {
+                    self.generate_expr(inner, locals, code);
+                    code.push(Instruction::Neg);
+                }
+            },
+            ExprKind::Unary {
+                op: UnaryOp::Not,
+                expr: inner,
+            } => {
+                self.generate_expr(inner, locals, code);
+                code.push(Instruction::BoolNot);
+            }
+            ExprKind::Call { callee, args } => {
+                self.generate_call(callee, None, args, locals, code);
+            }
+            ExprKind::MethodCall {
+                object,
+                method,
+                args,
+            } => {
+                let (name, receiver) = self.method_target(object, method, locals);
+                let receiver = if receiver { Some(&**object) } else { None };
+
+                self.generate_call(&name, receiver, args, locals, code);
+            }
+            ExprKind::Field { object, field } => {
+                let index = self.field(self.struct_of(object), field);
+
+                self.generate_expr(object, locals, code);
+                code.push(Instruction::GetField { index });
+            }
+            ExprKind::StructLiteral { name, fields } => {
+                self.generate_struct_literal(name, fields, locals, code);
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                self.generate_lambda(params, body, locals, code);
+            }
+        }
+    }
+
+    fn generate_struct_literal(
+        &self,
+        name: &str,
+        fields: &[FieldInit],
+        locals: &mut Locals,
+        code: &mut Code,
+    ) {
+        code.push(Instruction::Allocate {
+            fields: self.structs[name].len() as u32,
+        });
+
+        // Initializers run in source order, each field.set keeps the object on the stack
+        for init in fields.iter() {
+            let index = self.field(name, &init.name);
+
+            self.generate_expr(&init.value, locals, code);
+            code.push(Instruction::SetField { index });
+        }
+    }
+
+    fn generate_call(
+        &self,
+        callee: &str,
+        receiver: Option<&Expr>,
+        args: &[Expr],
+        locals: &mut Locals,
+        code: &mut Code,
+    ) {
+        // Variables shadow functions, calling one calls the function value it holds
+        if receiver.is_none() && locals.contains(callee) {
+            self.generate_load(callee, locals, code);
+
+            for arg in args.iter() {
+                self.generate_expr(arg, locals, code);
+            }
+
+            code.push(Instruction::CallIndirect {
+                param_count: args.len() as u32,
+            });
+
+            return;
+        }
+
+        // The receiver is passed as the first argument
+        for arg in receiver.into_iter().chain(args.iter()) {
+            self.generate_expr(arg, locals, code);
+        }
+
+        code.push(Instruction::Call {
+            module: self.functions[callee].module.clone(),
+            function: callee.to_string(),
+            param_count: (receiver.iter().count() + args.len()) as u32,
+        });
+    }
+}
+
+// Name a function is called by, `Struct.function` for functions of a struct
+fn qualified_name(function: &Function) -> String {
+    match &function.owner {
+        Some(owner) => format!("{}.{}", owner, function.name),
+        None => function.name.clone(),
+    }
+}
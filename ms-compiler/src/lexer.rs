@@ -0,0 +1,397 @@
+use crate::ast::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    // Literals
+    Identifier(String),
+    // Unsigned, the parser folds a leading minus into it so `-2147483648` is in range
+    Integer(u32),
+    Float(f32),
+    String(String),
+
+    // Keywords
+    Def,
+    Let,
+    If,
+    Else,
+    While,
+    Return,
+    Break,
+    Continue,
+    True,
+    False,
+    Extern,
+    Struct,
+
+    // Punctuation
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Colon,
+    Semicolon,
+    Dot,
+    Ellipsis,
+    Arrow,
+
+    // Operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Assign,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Not,
+    AndAnd,
+    OrOr,
+
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub(crate) struct Lexer {
+    source: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    pub(crate) fn new(source: &str) -> Lexer {
+        Lexer {
+            source: source.chars().collect(),
+            position: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub(crate) fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+        let mut tokens = vec![];
+
+        loop {
+            let token = self.next_token()?;
+            let eof = token.kind == TokenKind::Eof;
+
+            tokens.push(token);
+
+            if eof {
+                break;
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.position).copied()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.source.get(self.position + 1).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let char = self.peek()?;
+
+        self.position += 1;
+
+        if char == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(char)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(char) = self.peek() {
+            match char {
+                ' ' | '\n' | '\r' | '\t' => {
+                    self.advance();
+                }
+                '/' if self.peek_next() == Some('/') => {
+                    while let Some(char) = self.peek() {
+                        if char == '\n' {
+                            break;
+                        }
+
+                        self.advance();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, String> {
+        self.skip_whitespace();
+
+        let span = Span {
+            line: self.line,
+            column: self.column,
+        };
+
+        let Some(char) = self.advance() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                span,
+            });
+        };
+
+        let kind = match char {
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            ';' => TokenKind::Semicolon,
+            '+' => TokenKind::Plus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '.' => {
+                if self.peek() == Some('.') && self.peek_next() == Some('.') {
+                    self.advance();
+                    self.advance();
+                    TokenKind::Ellipsis
+                } else {
+                    TokenKind::Dot
+                }
+            }
+            '-' => {
+                if self.peek() == Some('>') {
+                    self.advance();
+                    TokenKind::Arrow
+                } else {
+                    TokenKind::Minus
+                }
+            }
+            '=' => self.either('=', TokenKind::Eq, TokenKind::Assign),
+            '<' => self.either('=', TokenKind::Le, TokenKind::Lt),
+            '>' => self.either('=', TokenKind::Ge, TokenKind::Gt),
+            '!' => {
+                if self.peek() == Some('=') {
+                    self.advance();
+                    TokenKind::Ne
+                } else {
+                    TokenKind::Not
+                }
+            }
+            '&' if self.peek() == Some('&') => {
+                self.advance();
+                TokenKind::AndAnd
+            }
+            '|' if self.peek() == Some('|') => {
+                self.advance();
+                TokenKind::OrOr
+            }
+            '"' => self.string(span)?,
+            '0'..='9' => self.number(char, span)?,
+            'a'..='z' | 'A'..='Z' | '_' => self.identifier(char),
+            _ => return Err(format!("{}: Unexpected character '{}'", span, char)),
+        };
+
+        Ok(Token { kind, span })
+    }
+
+    fn either(&mut self, next: char, matched: TokenKind, otherwise: TokenKind) -> TokenKind {
+        if self.peek() == Some(next) {
+            self.advance();
+            matched
+        } else {
+            otherwise
+        }
+    }
+
+    fn string(&mut self, span: Span) -> Result<TokenKind, String> {
+        let mut value = String::new();
+
+        loop {
+            let Some(char) = self.advance() else {
+                return Err(format!("{}: Unterminated string", span));
+            };
+
+            match char {
+                '"' => break,
+                '\\' => {
+                    let Some(escaped) = self.advance() else {
+                        return Err(format!("{}: Unterminated string", span));
+                    };
+
+                    match escaped {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        '0' => value.push('\0'),
+                        '\\' | '"' => value.push(escaped),
+                        _ => {
+                            return Err(format!(
+                                "{}: Invalid escape sequence '\\{}'",
+                                span, escaped
+                            ))
+                        }
+                    }
+                }
+                _ => value.push(char),
+            }
+        }
+
+        Ok(TokenKind::String(value))
+    }
+
+    fn number(&mut self, first: char, span: Span) -> Result<TokenKind, String> {
+        let mut text = String::from(first);
+        let mut is_float = false;
+
+        while let Some(char) = self.peek() {
+            if char.is_ascii_digit() {
+                text.push(char);
+            } else if char == '.'
+                && !is_float
+                && self.peek_next().is_some_and(|c| c.is_ascii_digit())
+            {
+                is_float = true;
+                text.push(char);
+            } else {
+                break;
+            }
+
+            self.advance();
+        }
+
+        if is_float {
+            text.parse::<f32>()
+                .map(TokenKind::Float)
+                .map_err(|_| format!("{}: Invalid float literal '{}'", span, text))
+        } else {
+            text.parse::<u32>()
+                .map(TokenKind::Integer)
+                .map_err(|_| format!("{}: Invalid integer literal '{}'", span, text))
+        }
+    }
+
+    fn identifier(&mut self, first: char) -> TokenKind {
+        let mut name = String::from(first);
+
+        while let Some(char) = self.peek() {
+            if char.is_ascii_alphanumeric() || char == '_' {
+                name.push(char);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+
+        match name.as_str() {
+            "def" => TokenKind::Def,
+            "let" => TokenKind::Let,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "while" => TokenKind::While,
+            "return" => TokenKind::Return,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "extern" => TokenKind::Extern,
+            "struct" => TokenKind::Struct,
+            _ => TokenKind::Identifier(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        Lexer::new(source)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn lexer_tokens() {
+        assert_eq!(
+            kinds("def add(a: int) -> int { return a + 1.5; } // comment"),
+            vec![
+                TokenKind::Def,
+                TokenKind::Identifier("add".to_string()),
+                TokenKind::LeftParen,
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Colon,
+                TokenKind::Identifier("int".to_string()),
+                TokenKind::RightParen,
+                TokenKind::Arrow,
+                TokenKind::Identifier("int".to_string()),
+                TokenKind::LeftBrace,
+                TokenKind::Return,
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Plus,
+                TokenKind::Float(1.5),
+                TokenKind::Semicolon,
+                TokenKind::RightBrace,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_operators() {
+        assert_eq!(
+            kinds("== != < <= > >= = - -> ..."),
+            vec![
+                TokenKind::Eq,
+                TokenKind::Ne,
+                TokenKind::Lt,
+                TokenKind::Le,
+                TokenKind::Gt,
+                TokenKind::Ge,
+                TokenKind::Assign,
+                TokenKind::Minus,
+                TokenKind::Arrow,
+                TokenKind::Ellipsis,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_spans() {
+        let tokens = Lexer::new("let\n  x").tokenize().unwrap();
+
+        assert_eq!(tokens[1].span, Span { line: 2, column: 3 });
+    }
+
+    #[test]
+    fn lexer_string_escapes() {
+        assert_eq!(
+            kinds("\"a\\n\\\"b\\\"\""),
+            vec![TokenKind::String("a\n\"b\"".to_string()), TokenKind::Eof]
+        );
+    }
+
+    #[test]
+    fn lexer_unterminated_string() {
+        assert!(Lexer::new("\"abc").tokenize().is_err());
+    }
+}
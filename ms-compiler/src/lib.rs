@@ -0,0 +1,419 @@
+pub mod ast;
+mod checker;
+mod codegen;
+mod lexer;
+mod parser;
+
+use checker::Checker;
+use codegen::Generator;
+use lexer::Lexer;
+use ms_runtime::{Code, Instruction};
+use parser::Parser;
+
+// Name of the module produced from a `.ms` source file
+pub const MAIN_MODULE: &str = "main";
+
+pub fn parse(source: &str) -> Result<ast::Program, String> {
+    let tokens = Lexer::new(source).tokenize()?;
+
+    Parser::new(tokens).parse()
+}
+
+// Compile `.ms` source into the same instruction tree `asm::assemble` produces.
+// Type errors are all reported at once, one `line:column: message` per line
+pub fn compile(source: &str) -> Result<Code, String> {
+    let program = parse(source)?;
+
+    let types = Checker::new().check(&program)?;
+
+    let mut code = vec![Instruction::Version {
+        major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
+        minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
+        patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
+    }];
+
+    code.extend(Generator::new(MAIN_MODULE, &types).generate(&program));
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ms_runtime::{load_modules, Value, VirtualMachine};
+
+    fn run(source: &str, function: &str, args: Vec<Value>) -> Option<Value> {
+        let code = compile(source).unwrap();
+        let (modules, _) = load_modules(&code).unwrap();
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        vm.link().unwrap();
+        vm.call(MAIN_MODULE, function, args).unwrap()
+    }
+
+    fn run_int(source: &str) -> i32 {
+        match run(source, "main", vec![]) {
+            Some(Value::Integer(value)) => value,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn compile_arithmetic() {
+        assert_eq!(
+            run_int("def main() -> int { return (10 - 4) / 2 * 3 + 1; }"),
+            10
+        );
+        assert_eq!(
+            run_int("def main() -> int { let x: int = 17; return -x % 5 + -(2 * 3); }"),
+            -8
+        );
+    }
+
+    #[test]
+    fn compile_locals_and_while() {
+        let source = "
+            def main() -> int {
+                let i = 0;
+                let total = 0;
+                while i < 5 {
+                    i = i + 1;
+                    if i == 2 { continue; }
+                    total = total + i;
+                }
+                return total;
+            }
+        ";
+
+        assert_eq!(run_int(source), 13);
+    }
+
+    #[test]
+    fn compile_nested_loops_break() {
+        let source = "
+            def main() -> int {
+                let count = 0;
+                let i = 0;
+                while i < 3 {
+                    let j = 0;
+                    while true {
+                        if j == 2 { break; }
+                        j = j + 1;
+                        count = count + 1;
+                    }
+                    i = i + 1;
+                }
+                return count;
+            }
+        ";
+
+        assert_eq!(run_int(source), 6);
+    }
+
+    #[test]
+    fn compile_calls_and_recursion() {
+        let source = "
+            def fib(n: int) -> int {
+                if n < 2 {
+                    return n;
+                } else {
+                    return fib(n - 1) + fib(n - 2);
+                }
+            }
+
+            def main() -> int {
+                return fib(10);
+            }
+        ";
+
+        assert_eq!(run_int(source), 55);
+    }
+
+    #[test]
+    fn compile_else_if_and_strings() {
+        let source = "
+            def describe(n: int) -> string {
+                if n < 0 {
+                    return \"negative\";
+                } else if n == 0 {
+                    return \"zero\";
+                }
+                return \"posi\" + \"tive\";
+            }
+        ";
+
+        let result = run(source, "describe", vec![Value::Integer(3)]);
+
+        assert!(matches!(result, Some(Value::String(s)) if s == "positive"));
+    }
+
+    #[test]
+    fn compile_logical_operators() {
+        // The division by zero on the right hand side is never evaluated
+        let source = "
+            def check(x: int) -> bool {
+                return x != 0 && 10 / x > 1 || !(x < 0);
+            }
+        ";
+
+        let check = |x| run(source, "check", vec![Value::Integer(x)]);
+
+        assert!(matches!(check(0), Some(Value::Boolean(true))));
+        assert!(matches!(check(2), Some(Value::Boolean(true))));
+        assert!(matches!(check(-20), Some(Value::Boolean(false))));
+        assert!(compile("def main() { let x = 1 && true; }")
+            .unwrap_err()
+            .contains("Operator '&&' cannot be applied to 'int' and 'bool'"));
+        assert!(compile("def main() { let x = !1; }")
+            .unwrap_err()
+            .contains("Operator '!' cannot be applied to 'int'"));
+    }
+
+    #[test]
+    fn compile_integer_limits() {
+        assert_eq!(
+            run_int("def main() -> int { return -2147483648; }"),
+            i32::MIN
+        );
+        assert_eq!(
+            run_int("def main() -> int { return 2147483647; }"),
+            i32::MAX
+        );
+        assert_eq!(
+            run_int("def main() -> int { return -2147483647 - 1; }"),
+            i32::MIN
+        );
+
+        assert!(compile("def main() -> int { return 2147483648; }")
+            .unwrap_err()
+            .contains("Integer literal '2147483648' is out of range"));
+        assert!(compile("def main() -> int { return -2147483649; }")
+            .unwrap_err()
+            .contains("Integer literal '-2147483649' is out of range"));
+    }
+
+    #[test]
+    fn compile_void_call_leaves_no_value() {
+        let source = "
+            def noop(x: int) { return; }
+            def main() { noop(1); 1 + 2; }
+        ";
+
+        assert!(run(source, "main", vec![]).is_none());
+    }
+
+    #[test]
+    fn compile_extern_module() {
+        let code =
+            compile("extern \"std.mod\" { def print(...); } def main() { print(1, 2); }").unwrap();
+
+        assert!(matches!(
+            &code[1],
+            Instruction::LoadModule { name, code } if name == "std.mod" && code.len() == 1
+        ));
+    }
+
+    #[test]
+    fn compile_struct_fields() {
+        let source = "
+            struct Point {
+                x: int
+                y: int
+            }
+
+            struct Line {
+                from: Point,
+                to: Point,
+            }
+
+            def length_x(line: Line) -> int {
+                return line.to.x - line.from.x;
+            }
+
+            def main() -> int {
+                let line = Line {
+                    to: Point { y: 0, x: 10 },
+                    from: Point { x: 1, y: 0 },
+                };
+                line.from.x = 4;
+                return length_x(line);
+            }
+        ";
+
+        assert_eq!(run_int(source), 6);
+    }
+
+    #[test]
+    fn compile_methods() {
+        let source = "
+            struct Counter {
+                count: int
+
+                def increment(self, by: int) -> Counter {
+                    self.count = self.count + by;
+                    return self;
+                }
+            }
+
+            def Counter.new(start: int) -> Counter {
+                return Counter { count: start };
+            }
+
+            def Counter.get(self) -> int {
+                return self.count;
+            }
+
+            def main() -> int {
+                let counter = Counter.new(1);
+                counter.increment(2).increment(3);
+                return counter.get() + Counter.get(counter);
+            }
+        ";
+
+        assert_eq!(run_int(source), 12);
+    }
+
+    #[test]
+    fn compile_method_errors() {
+        let source = "
+            struct Foo { boo: int }
+            def Foo.new() -> Foo { return Foo { boo: 0 }; }
+            def Foo.get(self) -> int { return self.boo; }
+        ";
+
+        assert!(
+            compile(&format!("{} def main() {{ Foo.new().new(); }}", source))
+                .unwrap_err()
+                .contains("'Foo.new' is not a method")
+        );
+        assert!(
+            compile(&format!("{} def main() {{ Foo.new().get(1); }}", source))
+                .unwrap_err()
+                .contains("expects 0 argument(s), found 1")
+        );
+        assert!(
+            compile(&format!("{} def main() {{ Foo.new().set(); }}", source))
+                .unwrap_err()
+                .contains("Struct 'Foo' has no function 'set'")
+        );
+        assert!(compile("def Bar.new() {}")
+            .unwrap_err()
+            .contains("Unknown struct 'Bar'"));
+    }
+
+    #[test]
+    fn compile_closures() {
+        let source = "
+            struct Counter {
+                step: int
+
+                def stepper(self) -> def(int) -> int {
+                    return def(x: int) -> int { return x + self.step; };
+                }
+            }
+
+            def make_adder(n: int) -> def(int) -> int {
+                return def(x: int) -> int { return x + n; };
+            }
+
+            def apply(f: def(int) -> int, x: int) -> int {
+                return f(x);
+            }
+
+            def twice(x: int) -> int { return x * 2; }
+
+            def main() -> int {
+                let base = 100;
+                let nested = def() -> int {
+                    let inner = def(y: int) -> int { return y + base; };
+                    return inner(1);
+                };
+                let ignore = def(x: int) {};
+                ignore(1);
+
+                let counter = Counter { step: 1000 };
+                return apply(make_adder(5), 1) + apply(twice, 10) + nested()
+                    + apply(counter.stepper(), 0);
+            }
+        ";
+
+        assert_eq!(run_int(source), 1127);
+
+        // Results of calls through variables keep their type
+        let source = "
+            struct Point {
+                x: int
+
+                def get(self) -> int { return self.x; }
+            }
+
+            struct Line { to: Point }
+
+            def main() -> int {
+                let f = def() -> def() -> int {
+                    return def() -> int { return 7; };
+                };
+                let g = f();
+                let point = def() -> Point { return Point { x: 3 }; };
+                let line = def(to: Point) -> Line { return Line { to: to }; };
+                let ignore = def(x: int) {};
+                ignore(g());
+                return g() + point().x + line(point()).to.get();
+            }
+        ";
+
+        assert_eq!(run_int(source), 13);
+
+        assert!(
+            compile("def main() { let x = 1; let f = def() { x = 2; }; }")
+                .unwrap_err()
+                .contains("Cannot assign to captured variable 'x'")
+        );
+        assert!(compile("def main() { let x = 1; x(); }")
+            .unwrap_err()
+            .contains("Cannot call 'x' of type 'int'"));
+        assert!(compile(
+            "def apply(f: def(int) -> int) -> int { return f(1); }\n\
+             def main() { apply(def(x: bool) -> int { return 1; }); }"
+        )
+        .unwrap_err()
+        .contains("Expected an argument of type 'def(int) -> int', found 'def(bool) -> int'"));
+    }
+
+    #[test]
+    fn compile_struct_errors() {
+        let source = "struct Foo { boo: int } def main() { let foo = Foo { boo: 1 }; ";
+
+        assert!(compile(&format!("{}foo.bar = 1; }}", source))
+            .unwrap_err()
+            .contains("Struct 'Foo' has no field 'bar'"));
+        assert!(
+            compile("struct Foo { boo: int } def main() { let foo = Foo {}; }")
+                .unwrap_err()
+                .contains("Missing field 'boo'")
+        );
+        assert!(compile("def main() { let x = 1; x.boo = 2; }")
+            .unwrap_err()
+            .contains("Type 'int' has no fields"));
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert!(compile("def main() { x = 1; }")
+            .unwrap_err()
+            .contains("Unknown variable 'x'"));
+        assert!(compile("def main() { foo(); }")
+            .unwrap_err()
+            .contains("Unknown function 'foo'"));
+        assert!(compile("def f(a: int) {} def main() { f(); }")
+            .unwrap_err()
+            .contains("expects 1 argument(s), found 0"));
+        assert!(compile("def main() { break; }")
+            .unwrap_err()
+            .contains("'break' outside of a loop"));
+    }
+}
@@ -0,0 +1,874 @@
+use crate::{
+    ast::{
+        BinaryOp, Expr, ExprKind, Extern, ExternFunction, Field, FieldInit, Function, FunctionType,
+        Item, Param, Program, Span, Stmt, Struct, TypeName, UnaryOp, SELF_PARAM,
+    },
+    lexer::{Token, TokenKind},
+};
+
+pub(crate) struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // `Foo { ... }` literals are ambiguous with blocks in `if`/`while` conditions
+    allow_struct_literal: bool,
+}
+
+impl Parser {
+    pub(crate) fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            position: 0,
+            allow_struct_literal: true,
+        }
+    }
+
+    pub(crate) fn parse(&mut self) -> Result<Program, String> {
+        let mut items = vec![];
+
+        while !self.check(&TokenKind::Eof) {
+            items.push(self.parse_item()?);
+        }
+
+        Ok(Program { items })
+    }
+
+    fn peek(&self) -> &Token {
+        // The lexer always terminates the stream with an Eof token
+        &self.tokens[self.position.min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+
+        token
+    }
+
+    fn check(&self, kind: &TokenKind) -> bool {
+        &self.peek().kind == kind
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.check(kind) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, what: &str) -> Result<Token, String> {
+        if self.check(kind) {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn expect_identifier(&mut self, what: &str) -> Result<(String, Span), String> {
+        let token = self.peek().clone();
+
+        if let TokenKind::Identifier(name) = token.kind {
+            self.advance();
+            Ok((name, token.span))
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn unexpected(&self, what: &str) -> String {
+        let token = self.peek();
+
+        format!("{}: Expected {}, found {:?}", token.span, what, token.kind)
+    }
+
+    fn parse_item(&mut self) -> Result<Item, String> {
+        match self.peek().kind {
+            TokenKind::Def => Ok(Item::Function(self.parse_function(None)?)),
+            TokenKind::Extern => Ok(Item::Extern(self.parse_extern()?)),
+            TokenKind::Struct => Ok(Item::Struct(self.parse_struct()?)),
+            _ => Err(self.unexpected("'def', 'struct' or 'extern'")),
+        }
+    }
+
+    // `owner` is set for functions declared inside a struct body
+    fn parse_function(&mut self, owner: Option<&str>) -> Result<Function, String> {
+        let span = self.expect(&TokenKind::Def, "'def'")?.span;
+        let (mut name, _) = self.expect_identifier("function name")?;
+        let mut owner = owner.map(|owner| owner.to_string());
+
+        // def Foo.new(...)
+        if owner.is_none() && self.eat(&TokenKind::Dot) {
+            owner = Some(name);
+            name = self.expect_identifier("function name")?.0;
+        }
+
+        self.expect(&TokenKind::LeftParen, "'('")?;
+        let (params, _) = self.parse_params(false, owner.as_deref())?;
+        let ret = self.parse_return_type()?;
+        let body = self.parse_block()?;
+
+        Ok(Function {
+            owner,
+            name,
+            params,
+            ret,
+            body,
+            span,
+        })
+    }
+
+    fn parse_struct(&mut self) -> Result<Struct, String> {
+        let span = self.expect(&TokenKind::Struct, "'struct'")?.span;
+        let (name, _) = self.expect_identifier("struct name")?;
+
+        self.expect(&TokenKind::LeftBrace, "'{'")?;
+
+        let mut fields = vec![];
+        let mut functions = vec![];
+
+        while !self.eat(&TokenKind::RightBrace) {
+            if self.check(&TokenKind::Def) {
+                functions.push(self.parse_function(Some(&name))?);
+                continue;
+            }
+
+            let (name, span) = self.expect_identifier("field name, 'def' or '}'")?;
+
+            self.expect(&TokenKind::Colon, "':'")?;
+            let ty = self.parse_type()?;
+
+            fields.push(Field { name, ty, span });
+
+            // Fields may be separated by commas or just by line breaks
+            self.eat(&TokenKind::Comma);
+        }
+
+        Ok(Struct {
+            name,
+            fields,
+            functions,
+            span,
+        })
+    }
+
+    fn parse_extern(&mut self) -> Result<Extern, String> {
+        let span = self.expect(&TokenKind::Extern, "'extern'")?.span;
+
+        let module = match self.advance().kind {
+            TokenKind::String(module) => module,
+            _ => {
+                return Err(format!(
+                    "{}: Expected module name string after 'extern'",
+                    span
+                ))
+            }
+        };
+
+        self.expect(&TokenKind::LeftBrace, "'{'")?;
+
+        let mut functions = vec![];
+
+        while !self.eat(&TokenKind::RightBrace) {
+            let span = self.expect(&TokenKind::Def, "'def' or '}'")?.span;
+            let (name, _) = self.expect_identifier("function name")?;
+
+            self.expect(&TokenKind::LeftParen, "'('")?;
+            let (params, variadic) = self.parse_params(true, None)?;
+            let ret = self.parse_return_type()?;
+
+            self.expect(&TokenKind::Semicolon, "';'")?;
+
+            functions.push(ExternFunction {
+                name,
+                params,
+                variadic,
+                ret,
+                span,
+            });
+        }
+
+        Ok(Extern {
+            module,
+            functions,
+            span,
+        })
+    }
+
+    // Parses the parameter list after the opening parenthesis, including the closing one.
+    // Functions of a struct (`owner`) may take an untyped `self` as first parameter.
+    fn parse_params(
+        &mut self,
+        allow_variadic: bool,
+        owner: Option<&str>,
+    ) -> Result<(Vec<Param>, bool), String> {
+        let mut params = vec![];
+        let mut variadic = false;
+
+        while !self.eat(&TokenKind::RightParen) {
+            if allow_variadic && self.eat(&TokenKind::Ellipsis) {
+                variadic = true;
+                self.expect(&TokenKind::RightParen, "')' after '...'")?;
+                break;
+            }
+
+            let (name, span) = self.expect_identifier("parameter name")?;
+
+            let ty = match owner {
+                Some(owner)
+                    if params.is_empty()
+                        && name == SELF_PARAM
+                        && !self.check(&TokenKind::Colon) =>
+                {
+                    TypeName {
+                        name: owner.to_string(),
+                        signature: None,
+                        span,
+                    }
+                }
+                _ => {
+                    self.expect(&TokenKind::Colon, "':'")?;
+                    self.parse_type()?
+                }
+            };
+
+            params.push(Param { name, ty, span });
+
+            if !self.check(&TokenKind::RightParen) {
+                self.expect(&TokenKind::Comma, "',' or ')'")?;
+            }
+        }
+
+        Ok((params, variadic))
+    }
+
+    fn parse_return_type(&mut self) -> Result<Option<TypeName>, String> {
+        if self.eat(&TokenKind::Arrow) {
+            Ok(Some(self.parse_type()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_type(&mut self) -> Result<TypeName, String> {
+        // def(int, int) -> int
+        if self.check(&TokenKind::Def) {
+            let span = self.advance().span;
+
+            self.expect(&TokenKind::LeftParen, "'('")?;
+
+            let mut params = vec![];
+
+            while !self.eat(&TokenKind::RightParen) {
+                params.push(self.parse_type()?);
+
+                if !self.check(&TokenKind::RightParen) {
+                    self.expect(&TokenKind::Comma, "',' or ')'")?;
+                }
+            }
+
+            let ret = self.parse_return_type()?;
+
+            return Ok(TypeName {
+                name: "def".to_string(),
+                signature: Some(Box::new(FunctionType { params, ret })),
+                span,
+            });
+        }
+
+        let (name, span) = self.expect_identifier("type name")?;
+
+        Ok(TypeName {
+            name,
+            signature: None,
+            span,
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect(&TokenKind::LeftBrace, "'{'")?;
+
+        let mut stmts = vec![];
+
+        while !self.eat(&TokenKind::RightBrace) {
+            if self.check(&TokenKind::Eof) {
+                return Err(self.unexpected("'}'"));
+            }
+
+            stmts.push(self.parse_stmt()?);
+        }
+
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, String> {
+        let span = self.peek().span;
+
+        match self.peek().kind {
+            TokenKind::Let => {
+                self.advance();
+                let (name, _) = self.expect_identifier("variable name")?;
+
+                let ty = if self.eat(&TokenKind::Colon) {
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+
+                self.expect(&TokenKind::Assign, "'='")?;
+                let value = self.parse_expr()?;
+                self.expect(&TokenKind::Semicolon, "';'")?;
+
+                Ok(Stmt::Let {
+                    name,
+                    ty,
+                    value,
+                    span,
+                })
+            }
+            TokenKind::If => self.parse_if(),
+            TokenKind::While => {
+                self.advance();
+                let condition = self.parse_condition()?;
+                let body = self.parse_block()?;
+
+                Ok(Stmt::While {
+                    condition,
+                    body,
+                    span,
+                })
+            }
+            TokenKind::Return => {
+                self.advance();
+
+                let value = if self.check(&TokenKind::Semicolon) {
+                    None
+                } else {
+                    Some(self.parse_expr()?)
+                };
+
+                self.expect(&TokenKind::Semicolon, "';'")?;
+
+                Ok(Stmt::Return { value, span })
+            }
+            TokenKind::Break => {
+                self.advance();
+                self.expect(&TokenKind::Semicolon, "';'")?;
+
+                Ok(Stmt::Break { span })
+            }
+            TokenKind::Continue => {
+                self.advance();
+                self.expect(&TokenKind::Semicolon, "';'")?;
+
+                Ok(Stmt::Continue { span })
+            }
+            _ => {
+                let expr = self.parse_expr()?;
+
+                if self.eat(&TokenKind::Assign) {
+                    let value = self.parse_expr()?;
+                    self.expect(&TokenKind::Semicolon, "';'")?;
+
+                    return Ok(Stmt::Assign {
+                        target: expr,
+                        value,
+                        span,
+                    });
+                }
+
+                self.expect(&TokenKind::Semicolon, "';'")?;
+
+                Ok(Stmt::Expr(expr))
+            }
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<Stmt, String> {
+        let span = self.expect(&TokenKind::If, "'if'")?.span;
+        let condition = self.parse_condition()?;
+        let then_block = self.parse_block()?;
+
+        let else_block = if self.eat(&TokenKind::Else) {
+            if self.check(&TokenKind::If) {
+                vec![self.parse_if()?]
+            } else {
+                self.parse_block()?
+            }
+        } else {
+            vec![]
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_block,
+            else_block,
+            span,
+        })
+    }
+
+    pub(crate) fn parse_expr(&mut self) -> Result<Expr, String> {
+        self.parse_or()
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, String> {
+        self.with_struct_literals(false, |parser| parser.parse_expr())
+    }
+
+    fn with_struct_literals<T>(
+        &mut self,
+        allow: bool,
+        parse: impl FnOnce(&mut Parser) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let saved = self.allow_struct_literal;
+
+        self.allow_struct_literal = allow;
+        let result = parse(self);
+        self.allow_struct_literal = saved;
+
+        result
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_and()?;
+
+        while self.check(&TokenKind::OrOr) {
+            let span = self.advance().span;
+            let rhs = self.parse_and()?;
+
+            lhs = binary(BinaryOp::Or, lhs, rhs, span);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_comparison()?;
+
+        while self.check(&TokenKind::AndAnd) {
+            let span = self.advance().span;
+            let rhs = self.parse_comparison()?;
+
+            lhs = binary(BinaryOp::And, lhs, rhs, span);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_additive()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Eq => BinaryOp::Eq,
+                TokenKind::Ne => BinaryOp::Ne,
+                TokenKind::Lt => BinaryOp::Lt,
+                TokenKind::Le => BinaryOp::Le,
+                TokenKind::Gt => BinaryOp::Gt,
+                TokenKind::Ge => BinaryOp::Ge,
+                _ => break,
+            };
+
+            let span = self.advance().span;
+            let rhs = self.parse_additive()?;
+
+            lhs = binary(op, lhs, rhs, span);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_multiplicative()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => break,
+            };
+
+            let span = self.advance().span;
+            let rhs = self.parse_multiplicative()?;
+
+            lhs = binary(op, lhs, rhs, span);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                TokenKind::Percent => BinaryOp::Rem,
+                _ => break,
+            };
+
+            let span = self.advance().span;
+            let rhs = self.parse_unary()?;
+
+            lhs = binary(op, lhs, rhs, span);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek().kind {
+            TokenKind::Minus => Some(UnaryOp::Neg),
+            TokenKind::Not => Some(UnaryOp::Not),
+            _ => None,
+        };
+
+        if let Some(op) = op {
+            let span = self.advance().span;
+
+            if let (UnaryOp::Neg, TokenKind::Integer(value)) = (op, &self.peek().kind) {
+                let value = integer(-i64::from(*value), span)?;
+                self.advance();
+
+                return Ok(Expr {
+                    kind: ExprKind::Integer(value),
+                    span,
+                });
+            }
+
+            let expr = self.parse_unary()?;
+
+            return Ok(Expr {
+                kind: ExprKind::Unary {
+                    op,
+                    expr: Box::new(expr),
+                },
+                span,
+            });
+        }
+
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_primary()?;
+
+        while self.check(&TokenKind::Dot) {
+            let span = self.advance().span;
+            let (field, _) = self.expect_identifier("field name")?;
+
+            let kind = if self.eat(&TokenKind::LeftParen) {
+                ExprKind::MethodCall {
+                    object: Box::new(expr),
+                    method: field,
+                    args: self.parse_args()?,
+                }
+            } else {
+                ExprKind::Field {
+                    object: Box::new(expr),
+                    field,
+                }
+            };
+
+            expr = Expr { kind, span };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let token = self.peek().clone();
+        let span = token.span;
+
+        let kind = match token.kind {
+            TokenKind::Integer(value) => {
+                self.advance();
+                ExprKind::Integer(integer(value.into(), span)?)
+            }
+            TokenKind::Float(value) => {
+                self.advance();
+                ExprKind::Float(value)
+            }
+            TokenKind::String(value) => {
+                self.advance();
+                ExprKind::String(value)
+            }
+            TokenKind::True => {
+                self.advance();
+                ExprKind::Boolean(true)
+            }
+            TokenKind::False => {
+                self.advance();
+                ExprKind::Boolean(false)
+            }
+            TokenKind::Def => {
+                self.advance();
+                self.expect(&TokenKind::LeftParen, "'('")?;
+
+                let (params, _) = self.parse_params(false, None)?;
+                let ret = self.parse_return_type()?;
+                let body = self.with_struct_literals(true, |parser| parser.parse_block())?;
+
+                ExprKind::Lambda { params, ret, body }
+            }
+            TokenKind::LeftParen => {
+                self.advance();
+                let expr = self.with_struct_literals(true, |parser| parser.parse_expr())?;
+                self.expect(&TokenKind::RightParen, "')'")?;
+
+                return Ok(expr);
+            }
+            TokenKind::Identifier(name) => {
+                self.advance();
+
+                if self.eat(&TokenKind::LeftParen) {
+                    ExprKind::Call {
+                        callee: name,
+                        args: self.parse_args()?,
+                    }
+                } else if self.allow_struct_literal && self.check(&TokenKind::LeftBrace) {
+                    self.advance();
+
+                    ExprKind::StructLiteral {
+                        name,
+                        fields: self.parse_field_inits()?,
+                    }
+                } else {
+                    ExprKind::Identifier(name)
+                }
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+
+        Ok(Expr { kind, span })
+    }
+
+    // Parses `name: value` pairs after the opening brace, including the closing one
+    fn parse_field_inits(&mut self) -> Result<Vec<FieldInit>, String> {
+        let mut fields = vec![];
+
+        while !self.eat(&TokenKind::RightBrace) {
+            let (name, span) = self.expect_identifier("field name or '}'")?;
+
+            self.expect(&TokenKind::Colon, "':'")?;
+            let value = self.with_struct_literals(true, |parser| parser.parse_expr())?;
+
+            fields.push(FieldInit { name, value, span });
+
+            if !self.check(&TokenKind::RightBrace) {
+                self.expect(&TokenKind::Comma, "',' or '}'")?;
+            }
+        }
+
+        Ok(fields)
+    }
+
+    // Parses call arguments after the opening parenthesis, including the closing one
+    fn parse_args(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = vec![];
+
+        while !self.eat(&TokenKind::RightParen) {
+            args.push(self.with_struct_literals(true, |parser| parser.parse_expr())?);
+
+            if !self.check(&TokenKind::RightParen) {
+                self.expect(&TokenKind::Comma, "',' or ')'")?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr, span: Span) -> Expr {
+    Expr {
+        kind: ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+        span,
+    }
+}
+
+// Integer literal value, with the minus folded in for negative literals
+fn integer(value: i64, span: Span) -> Result<i32, String> {
+    i32::try_from(value)
+        .map_err(|_| format!("{}: Integer literal '{}' is out of range", span, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(source: &str) -> Result<Program, String> {
+        Parser::new(Lexer::new(source).tokenize()?).parse()
+    }
+
+    #[test]
+    fn parser_precedence() {
+        let mut parser = Parser::new(Lexer::new("1 + 2 * 3 < 4").tokenize().unwrap());
+        let expr = parser.parse_expr().unwrap();
+
+        let ExprKind::Binary { op, lhs, .. } = expr.kind else {
+            panic!("Expected binary expression");
+        };
+
+        assert_eq!(op, BinaryOp::Lt);
+
+        let ExprKind::Binary { op, rhs, .. } = lhs.kind else {
+            panic!("Expected binary expression");
+        };
+
+        assert_eq!(op, BinaryOp::Add);
+        assert!(matches!(
+            rhs.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Mul,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parser_function() {
+        let program =
+            parse("def add(a: int, b: int) -> int {\n    let c = a + b;\n    return c;\n}")
+                .unwrap();
+
+        let Item::Function(function) = &program.items[0] else {
+            panic!("Expected function");
+        };
+
+        assert_eq!(function.name, "add");
+        assert_eq!(function.params.len(), 2);
+        assert_eq!(function.ret.as_ref().unwrap().name, "int");
+        assert_eq!(function.body.len(), 2);
+    }
+
+    #[test]
+    fn parser_extern() {
+        let program = parse("extern \"std.mod\" { def print(...); }").unwrap();
+
+        let Item::Extern(ext) = &program.items[0] else {
+            panic!("Expected extern");
+        };
+
+        assert_eq!(ext.module, "std.mod");
+        assert!(ext.functions[0].variadic);
+    }
+
+    #[test]
+    fn parser_struct_and_literal() {
+        let program = parse(
+            "struct Foo {\n    boo: int\n    bar: string\n}\n\ndef main() {\n    let foo = Foo { boo: 1, bar: \"x\" };\n    if foo.boo == 1 { foo.boo = 2; }\n}",
+        )
+        .unwrap();
+
+        let Item::Struct(foo) = &program.items[0] else {
+            panic!("Expected struct");
+        };
+
+        assert_eq!(foo.fields.len(), 2);
+
+        let Item::Function(main) = &program.items[1] else {
+            panic!("Expected function");
+        };
+
+        let Stmt::Let { value, .. } = &main.body[0] else {
+            panic!("Expected let");
+        };
+
+        assert!(matches!(&value.kind, ExprKind::StructLiteral { fields, .. } if fields.len() == 2));
+
+        let Stmt::If { then_block, .. } = &main.body[1] else {
+            panic!("Expected if");
+        };
+
+        assert!(matches!(
+            &then_block[0],
+            Stmt::Assign {
+                target: Expr {
+                    kind: ExprKind::Field { .. },
+                    ..
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parser_methods() {
+        let program = parse(
+            "struct Foo {\n    boo: int\n\n    def get(self) -> int { return self.boo; }\n}\n\ndef Foo.new(value: int) -> Foo { return Foo { boo: value }; }\n\ndef main() { Foo.new(1).get(); }",
+        )
+        .unwrap();
+
+        let Item::Struct(foo) = &program.items[0] else {
+            panic!("Expected struct");
+        };
+
+        assert_eq!(foo.functions[0].owner.as_deref(), Some("Foo"));
+        assert_eq!(foo.functions[0].params[0].ty.name, "Foo");
+
+        let Item::Function(new) = &program.items[1] else {
+            panic!("Expected function");
+        };
+
+        assert_eq!(new.owner.as_deref(), Some("Foo"));
+        assert_eq!(new.name, "new");
+
+        let Item::Function(main) = &program.items[2] else {
+            panic!("Expected function");
+        };
+
+        let Stmt::Expr(call) = &main.body[0] else {
+            panic!("Expected expression statement");
+        };
+
+        let ExprKind::MethodCall { object, method, .. } = &call.kind else {
+            panic!("Expected method call");
+        };
+
+        assert_eq!(method, "get");
+        assert!(matches!(&object.kind, ExprKind::MethodCall { method, .. } if method == "new"));
+    }
+
+    #[test]
+    fn parser_lambda() {
+        let program = parse(
+            "def apply(f: def(int) -> int, x: int) -> int { return f(x); }\n\ndef main() { let g = def(a: int) -> int { return a; }; }",
+        )
+        .unwrap();
+
+        let Item::Function(apply) = &program.items[0] else {
+            panic!("Expected function");
+        };
+
+        assert_eq!(apply.params[0].ty.to_string(), "def(int) -> int");
+
+        let Item::Function(main) = &program.items[1] else {
+            panic!("Expected function");
+        };
+
+        let Stmt::Let { value, .. } = &main.body[0] else {
+            panic!("Expected let");
+        };
+
+        assert!(matches!(
+            &value.kind,
+            ExprKind::Lambda { params, ret: Some(_), body } if params.len() == 1 && body.len() == 1
+        ));
+    }
+
+    #[test]
+    fn parser_error_position() {
+        let error = parse("def main() {\n    let = 1;\n}").unwrap_err();
+
+        assert!(error.starts_with("2:9:"), "{}", error);
+    }
+}
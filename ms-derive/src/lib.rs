@@ -0,0 +1,111 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Index};
+
+// Structs convert to script objects with one field per struct field, in declaration
+// order, the layout the compiler gives script structs
+
+#[proc_macro_derive(ToValue)]
+pub fn derive_to_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match struct_fields(&input) {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let name = &input.ident;
+    let generics = bound(&input.generics, quote!(::ms_runtime::ToValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let pushes = fields
+        .iter()
+        .map(|field| quote!(object.push(self.#field)?;));
+
+    quote! {
+        impl #impl_generics ::ms_runtime::ToValue for #name #ty_generics #where_clause {
+            fn to_value(
+                self,
+                vm: &mut ::ms_runtime::VirtualMachine,
+            ) -> ::std::result::Result<::ms_runtime::Value, ::ms_runtime::ErrorKind> {
+                let mut object = ::ms_runtime::ObjectBuilder::new(vm);
+                #(#pushes)*
+                object.finish(::ms_runtime::Object::Values)
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(FromValue)]
+pub fn derive_from_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match struct_fields(&input) {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let name = &input.ident;
+    let generics = bound(&input.generics, quote!(::ms_runtime::FromValue));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let count = fields.len();
+    let values = fields.iter().enumerate().map(|(index, field)| {
+        quote!(#field: ::ms_runtime::FromValue::from_value(fields[#index].clone(), vm)?)
+    });
+
+    quote! {
+        impl #impl_generics ::ms_runtime::FromValue for #name #ty_generics #where_clause {
+            fn from_value(
+                value: ::ms_runtime::Value,
+                vm: &::ms_runtime::VirtualMachine,
+            ) -> ::std::result::Result<Self, ::ms_runtime::ErrorKind> {
+                let fields = ::ms_runtime::object_fields(&value, vm, #count)?;
+
+                ::std::result::Result::Ok(#name { #(#values),* })
+            }
+        }
+    }
+    .into()
+}
+
+// Member names of the struct's fields, indices for tuple structs
+fn struct_fields(input: &DeriveInput) -> syn::Result<Vec<TokenStream2>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Only structs can be converted to and from script values",
+        ));
+    };
+
+    Ok(match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = &field.ident;
+                quote!(#name)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let index = Index::from(index);
+                quote!(#index)
+            })
+            .collect(),
+        Fields::Unit => vec![],
+    })
+}
+
+// The struct's generics with `bound` required of every type parameter
+fn bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+
+    generics
+}
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+use ms_runtime::{asm::assemble, load_modules, Value, VirtualMachine};
+
+// Recursive fib, every run makes ~250k script calls
+const FIB: &str = "
+(mod main
+    (fn fib
+        (local.get 0)
+        (i32.const 2)
+        (cmp.lt)
+        (then (local.get 0) (return))
+
+        (local.get 0)
+        (i32.const 1)
+        (op.sub)
+        (call main fib 1)
+
+        (local.get 0)
+        (i32.const 2)
+        (op.sub)
+        (call main fib 1)
+
+        (op.add)
+    )
+)";
+
+// Baseline, best of 10 `cargo bench --bench call` (release) runs on the same machine:
+//   e233738  code cloned on every call                ~103ms per run
+//   7d28090  code shared between calls                ~44ms per run
+//   a539af9  calls linked to a function table         ~38ms per run
+//   b20f162  current tree                             ~36ms per run
+const RUNS: u32 = 10;
+
+fn main() {
+    let (modules, _) = load_modules(&assemble(FIB).unwrap()).unwrap();
+    let mut vm = VirtualMachine::new();
+
+    for module in modules {
+        vm.add_module(module);
+    }
+
+    vm.link().unwrap();
+
+    let start = Instant::now();
+
+    for _ in 0..RUNS {
+        let result = vm.call("main", "fib", vec![Value::Integer(25)]).unwrap();
+
+        assert!(matches!(result, Some(Value::Integer(75025))));
+    }
+
+    println!("fib(25): {:?} per run", start.elapsed() / RUNS);
+}
@@ -0,0 +1,91 @@
+/* MintScript native plugin interface, generated by `ms header`. Do not edit. */
+
+#ifndef MINTSCRIPT_H
+#define MINTSCRIPT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MS_ABI_VERSION 1
+
+typedef uint32_t MsType;
+
+#define MS_NULL 0
+#define MS_BOOL 1
+#define MS_INT 2
+#define MS_LONG 3
+#define MS_FLOAT 4
+#define MS_DOUBLE 5
+#define MS_STRING 6
+#define MS_OBJECT 7
+
+/* A value, only valid until the native function it was given to or created in returns */
+typedef struct MsValue MsValue;
+/* A call of a native function */
+typedef struct MsCall MsCall;
+/* Functions registered by a plugin */
+typedef struct MsRegistry MsRegistry;
+
+/* A native function. Returns one of its arguments, a value created with the new_* functions or NULL to return nothing */
+typedef const MsValue *(*MsNativeFn)(MsCall *call);
+
+/* Functions of the runtime, passed to ms_plugin_init */
+typedef struct MsApi {
+    uint32_t version;
+    /* Number of arguments passed to the call */
+    size_t (*arg_count)(MsCall *call);
+    /* Argument at `index`, NULL past the last argument */
+    const MsValue *(*arg)(MsCall *call, size_t index);
+    /* Type of a value, one of the MS_* types. NULL is MS_NULL */
+    MsType (*type_of)(const MsValue *value);
+    /* Value of a bool, false for other types */
+    bool (*as_bool)(const MsValue *value);
+    /* Value of an int, 0 for other types */
+    int32_t (*as_int)(const MsValue *value);
+    /* Value of an int or a long, 0 for other types */
+    int64_t (*as_long)(const MsValue *value);
+    /* Value of a float or a double, 0 for other types */
+    double (*as_double)(const MsValue *value);
+    /* UTF-8 bytes of a string, which are not NUL terminated, and their number in `length`. NULL for other types */
+    const char *(*as_string)(const MsValue *value, size_t *length);
+    /* New null value */
+    const MsValue *(*new_null)(MsCall *call);
+    /* New bool value */
+    const MsValue *(*new_bool)(MsCall *call, bool value);
+    /* New int value */
+    const MsValue *(*new_int)(MsCall *call, int32_t value);
+    /* New long value */
+    const MsValue *(*new_long)(MsCall *call, int64_t value);
+    /* New float value */
+    const MsValue *(*new_float)(MsCall *call, float value);
+    /* New double value */
+    const MsValue *(*new_double)(MsCall *call, double value);
+    /* New string from `length` bytes of UTF-8, invalid sequences are replaced */
+    const MsValue *(*new_string)(MsCall *call, const char *bytes, size_t length);
+    /* Fail the call with a runtime error once the native function returns, its return value is ignored */
+    void (*raise)(MsCall *call, const char *message);
+    /* Register a function of the plugin, modules load it by name with (fn.get) */
+    void (*register_fn)(MsRegistry *registry, const char *name, MsNativeFn function);
+} MsApi;
+
+#ifdef _WIN32
+#define MS_EXPORT __declspec(dllexport)
+#else
+#define MS_EXPORT __attribute__((visibility("default")))
+#endif
+
+/* Exported by plugins. Returns MS_ABI_VERSION, the plugin is only loaded by runtimes implementing it */
+MS_EXPORT uint32_t ms_plugin_abi_version(void);
+/* Exported by plugins. Registers the plugin's functions, returns 0 on success */
+MS_EXPORT int32_t ms_plugin_init(const MsApi *api, MsRegistry *registry);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,192 @@
+use crate::ErrorKind;
+
+// What integer arithmetic does when the result doesn't fit the operands' type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    // Keep the low bits of the result, two's complement wrap around
+    Wrapping,
+    // Raise an `IntegerOverflow` runtime error
+    #[default]
+    Checked,
+    // Clamp the result to the type's minimum or maximum
+    Saturating,
+}
+
+// The integer widths the VM computes with, i32 and i64
+pub(crate) trait Integer: Copy + PartialEq {
+    const ZERO: Self;
+
+    fn wrapping(self, rhs: Self, op: Op) -> Self;
+    fn checked(self, rhs: Self, op: Op) -> Option<Self>;
+    fn saturating(self, rhs: Self, op: Op) -> Self;
+    fn remainder(self, rhs: Self) -> Self;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    pub(crate) fn float<T>(self, a: T, b: T) -> T
+    where
+        T: std::ops::Add<Output = T>
+            + std::ops::Sub<Output = T>
+            + std::ops::Mul<Output = T>
+            + std::ops::Div<Output = T>,
+    {
+        match self {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div => a / b,
+        }
+    }
+}
+
+macro_rules! integer {
+    ($($ty:ty),*) => {$(
+        impl Integer for $ty {
+            const ZERO: Self = 0;
+
+            fn wrapping(self, rhs: Self, op: Op) -> Self {
+                match op {
+                    Op::Add => self.wrapping_add(rhs),
+                    Op::Sub => self.wrapping_sub(rhs),
+                    Op::Mul => self.wrapping_mul(rhs),
+                    Op::Div => self.wrapping_div(rhs),
+                }
+            }
+
+            fn checked(self, rhs: Self, op: Op) -> Option<Self> {
+                match op {
+                    Op::Add => self.checked_add(rhs),
+                    Op::Sub => self.checked_sub(rhs),
+                    Op::Mul => self.checked_mul(rhs),
+                    Op::Div => self.checked_div(rhs),
+                }
+            }
+
+            fn saturating(self, rhs: Self, op: Op) -> Self {
+                match op {
+                    Op::Add => self.saturating_add(rhs),
+                    Op::Sub => self.saturating_sub(rhs),
+                    Op::Mul => self.saturating_mul(rhs),
+                    Op::Div => self.saturating_div(rhs),
+                }
+            }
+
+            // MIN % -1 is 0, it only overflows as a step of the division
+            fn remainder(self, rhs: Self) -> Self {
+                self.wrapping_rem(rhs)
+            }
+        }
+    )*};
+}
+
+integer!(i32, i64);
+
+impl Overflow {
+    pub(crate) fn apply<T: Integer>(self, a: T, b: T, op: Op) -> Result<T, ErrorKind> {
+        if matches!(op, Op::Div) && b == T::ZERO {
+            return Err(ErrorKind::DivisionByZero);
+        }
+
+        match self {
+            Overflow::Wrapping => Ok(a.wrapping(b, op)),
+            Overflow::Checked => a.checked(b, op).ok_or(ErrorKind::IntegerOverflow),
+            Overflow::Saturating => Ok(a.saturating(b, op)),
+        }
+    }
+
+    pub(crate) fn neg<T: Integer>(self, a: T) -> Result<T, ErrorKind> {
+        self.apply(T::ZERO, a, Op::Sub)
+    }
+
+    // Remainder of the division rounding toward zero, it has the sign of `a`
+    pub(crate) fn rem<T: Integer>(self, a: T, b: T) -> Result<T, ErrorKind> {
+        if b == T::ZERO {
+            return Err(ErrorKind::DivisionByZero);
+        }
+
+        Ok(a.remainder(b))
+    }
+
+    // Remainder of the division rounding toward negative infinity, it has the sign of `b`
+    pub(crate) fn modulo<T: Integer + PartialOrd>(self, a: T, b: T) -> Result<T, ErrorKind> {
+        let rem = self.rem(a, b)?;
+
+        if rem != T::ZERO && (rem < T::ZERO) != (b < T::ZERO) {
+            // |rem| < |b| and they have different signs, this can't overflow
+            return Ok(rem.wrapping(b, Op::Add));
+        }
+
+        Ok(rem)
+    }
+}
+
+// Floored modulo for floats, the counterpart of `Overflow::modulo`
+pub(crate) fn float_modulo<T>(a: T, b: T) -> T
+where
+    T: Copy + PartialOrd + Default + std::ops::Rem<Output = T> + std::ops::Add<Output = T>,
+{
+    let rem = a % b;
+    let zero = T::default();
+
+    if rem != zero && (rem < zero) != (b < zero) {
+        rem + b
+    } else {
+        rem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflow_modes() {
+        let max = i32::MAX;
+
+        assert_eq!(Overflow::Wrapping.apply(max, 1, Op::Add), Ok(i32::MIN));
+        assert_eq!(
+            Overflow::Checked.apply(max, 1, Op::Add),
+            Err(ErrorKind::IntegerOverflow)
+        );
+        assert_eq!(Overflow::Saturating.apply(max, 1, Op::Add), Ok(max));
+        assert_eq!(
+            Overflow::Saturating.apply(i64::MIN, -1, Op::Div),
+            Ok(i64::MAX)
+        );
+        assert_eq!(Overflow::Wrapping.neg(i32::MIN), Ok(i32::MIN));
+        assert_eq!(
+            Overflow::Checked.neg(i32::MIN),
+            Err(ErrorKind::IntegerOverflow)
+        );
+
+        for overflow in [Overflow::Wrapping, Overflow::Checked, Overflow::Saturating] {
+            assert_eq!(
+                overflow.apply(1, 0, Op::Div),
+                Err(ErrorKind::DivisionByZero)
+            );
+            assert_eq!(overflow.rem(1, 0), Err(ErrorKind::DivisionByZero));
+            assert_eq!(overflow.rem(i32::MIN, -1), Ok(0));
+        }
+    }
+
+    #[test]
+    fn remainder_signs() {
+        let overflow = Overflow::Checked;
+
+        assert_eq!(overflow.rem(-7, 3), Ok(-1));
+        assert_eq!(overflow.rem(7, -3), Ok(1));
+        assert_eq!(overflow.modulo(-7, 3), Ok(2));
+        assert_eq!(overflow.modulo(7, -3), Ok(-2));
+        assert_eq!(overflow.modulo(-6, 3), Ok(0));
+        assert_eq!(float_modulo(-7.5f64, 2.0), 0.5);
+        assert_eq!(float_modulo(7.5f32, -2.0), -0.5);
+    }
+}
@@ -59,6 +59,18 @@ impl<'a> ByteReader<'a> {
         Some(f32::from_le_bytes([bytes[3], bytes[2], bytes[1], bytes[0]]))
     }
 
+    pub fn read_i64(&mut self) -> Option<i64> {
+        let bytes = self.read_bytes(8)?;
+
+        Some(i64::from_be_bytes(bytes.try_into().ok()?))
+    }
+
+    pub fn read_f64(&mut self) -> Option<f64> {
+        let bytes = self.read_bytes(8)?;
+
+        Some(f64::from_be_bytes(bytes.try_into().ok()?))
+    }
+
     pub fn read_bool(&mut self) -> Option<bool> {
         let byte = self.read_byte()?;
 
@@ -126,6 +138,15 @@ mod tests {
         assert_eq!(reader.read_u32(), Some(0x89ABCDEF));
     }
 
+    #[test]
+    fn module_reader_read_i64() {
+        let source = (-5i64).to_be_bytes().to_vec();
+        let mut reader = ByteReader::new(&source);
+
+        assert_eq!(reader.read_i64(), Some(-5));
+        assert_eq!(reader.read_f64(), None);
+    }
+
     #[test]
     fn module_reader_read_u32_out_of_bounds() {
         let source = vec![0x89, 0xAB, 0xCD];
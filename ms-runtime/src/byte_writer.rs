@@ -31,6 +31,16 @@ impl<'a> ByteWriter<'a> {
         self.source.extend(value.to_be_bytes().iter());
     }
 
+    #[inline]
+    pub fn write_i64(&mut self, value: i64) {
+        self.source.extend(value.to_be_bytes().iter());
+    }
+
+    #[inline]
+    pub fn write_f64(&mut self, value: f64) {
+        self.source.extend(value.to_be_bytes().iter());
+    }
+
     #[inline]
     pub fn write_bool(&mut self, value: bool) {
         self.source.push(if value { 1 } else { 0 });
@@ -8,16 +8,21 @@ pub enum ByteCode {
     // Debugging
     Dump = 0x01, // Dump the stack
     Hi = 0x02,   // Print "Hi"
+    Line = 0x20, // LINE <line: u32> Source line of the following instructions
 
     // Functions
-    Func = 0x03, // Define a function
-    Call = 0x04, // Call a function
+    Func = 0x03,   // Define a function
+    Call = 0x04,   // Call a function
+    Params = 0x21, // PARAMS <count: u32> Declare the number of parameters of the function
+    Invoke = 0x22, // INVOKE <index: u32> <param_count: u32> Call a function of the VM's function table, produced by linking
 
     // Constants
     PushConstString = 0x40, // Push a constant string onto the stack PushConstString <len: u32> <string: [u8; len]>
     PushConstInteger = 0x41, // Push a constant integer onto the stack PushConstInt <value: i32>
     PushConstFloat = 0x42,  // Push a constant float onto the stack PushConstFloat <value: f32>
     PushConstBoolean = 0x43, // Push a constant boolean onto the stack PushConstBoolean <value: bool>
+    PushConstLong = 0x44,    // Push a constant long onto the stack PushConstLong <value: i64>
+    PushConstDouble = 0x45,  // Push a constant double onto the stack PushConstDouble <value: f64>
 
     // Locals variables
     GetLocal = 0x09,     // Load a local variable onto the stack
@@ -40,6 +45,75 @@ pub enum ByteCode {
     Div = 0x10, // Divide
     Inc = 0x1D, // Increment
     Dec = 0x1E, // Decrement
+    Rem = 0x27, // Remainder of the division rounding toward zero
+    Mod = 0x28, // Remainder of the division rounding toward negative infinity
+    Neg = 0x29, // Negate
+
+    // Bitwise and logical
+    And = 0x2A,     // Bitwise and of integers, logical and of booleans
+    Or = 0x2B,      // Bitwise or of integers, logical or of booleans
+    Xor = 0x2C,     // Bitwise xor of integers, logical xor of booleans
+    Not = 0x2D,     // Bitwise complement of an integer
+    Shl = 0x2E,     // Shift an integer left
+    Shr = 0x2F,     // Shift an integer right, keeping its sign
+    BoolNot = 0x30, // Negate a boolean
+
+    // Lists
+    ListNew = 0x31,  // Push a new empty list
+    ListPush = 0x32, // Append a value to a list
+    ListPop = 0x33,  // Remove the last element of a list
+    ListLen = 0x34,  // Length of a list
+    ListGet = 0x35,  // Get the element at an index of a list
+    ListSet = 0x36,  // Set the element at an index of a list
+
+    // Maps
+    MapNew = 0x37,      // Push a new empty map
+    MapInsert = 0x38,   // Insert a key and value into a map
+    MapGet = 0x39,      // Get the value of a key of a map
+    MapRemove = 0x3A,   // Remove a key from a map
+    MapContains = 0x3B, // Whether a map has a key
+    MapLen = 0x3C,      // Number of entries of a map
+    MapKeys = 0x3D,     // List of the keys of a map
+
+    // Strings, indexed by character
+    StringLength = 0x46,       // Number of characters of a string
+    StringSubstring = 0x47,    // Characters between two indices of a string
+    StringIndexOf = 0x48,      // Index of the first occurrence of a string in another
+    StringSplit = 0x49,        // Split a string by a separator into a list
+    StringTrim = 0x4A,         // Remove leading and trailing whitespace
+    StringUpper = 0x4B,        // Convert a string to upper case
+    StringLower = 0x4C,        // Convert a string to lower case
+    StringCharCode = 0x4D,     // Code point of a character of a string
+    StringFromCharCode = 0x4E, // String of the character of a code point
+    StringRepeat = 0x4F,       // Repeat a string
+
+    // Conversion and introspection
+    ToString = 0x50,  // Format a value as a string
+    ToInteger = 0x51, // Convert a number, boolean or string to an i32
+    ToFloat = 0x52,   // Convert a number, boolean or string to an f32
+    TypeOf = 0x53,    // Name of the type of a value
+    IsNull = 0x54,    // Whether a value is null
+
+    // Function values
+    FunctionRef = 0x55, // FNREF <module: string> <name: string> <captures: u32> Push a function value
+    GetUpvalue = 0x56,  // UPVALGET <index: u32> Push a value captured by the running closure
+    CallIndirect = 0x57, // CALLIND <param_count: u32> Call the function value below the arguments
+
+    // Globals
+    Global = 0x58, // GLOBAL <length: u32> <name: string> <init: [ByteCode x length]> Declare a global
+    GetGlobal = 0x59, // GLOBALGET <name: string> Push the value of a global
+    SetGlobal = 0x5A, // GLOBALSET <name: string> Pop a value into a global
+
+    // Native objects
+    GetProperty = 0x5B, // PROPGET <name: string> Replace a native object with one of its properties
+    SetProperty = 0x5C, // PROPSET <name: string> Pop a value into a property of a native object
+    CallMethod = 0x5D, // CALLMETHOD <name: string> <param_count: u32> Call a method of a native object
+
+    // Numeric conversions
+    ConvertInteger = 0x23, // Convert the number on top of the stack to an i32
+    ConvertLong = 0x24,    // Convert the number on top of the stack to an i64
+    ConvertFloat = 0x25,   // Convert the number on top of the stack to an f32
+    ConvertDouble = 0x26,  // Convert the number on top of the stack to an f64
 
     // Comparison
     Eq = 0x11, // Equal
@@ -52,18 +126,28 @@ pub enum ByteCode {
     // Modules
     Module = 0x1B, // Define a module
 
+    // Structs
+    Struct = 0x1F, // STRUCT <length: u32> <name: string> <code: [ByteCode x length]> Define the functions of a struct
+
     // Dynamic Module
     LoadModule = 0x19,  // Load a dynamic module
     GetFunction = 0x1A, // Get a function from a dynamic module
     Alias = 0x1C,       // Alias a function from a dynamic module
 
     // Control flow
-    Return = 0xFE,   // Return from the current function
-    Then = 0xFD,     // THEN <block: [ByteCode]> END Execute a block of code conditionally
+    Return = 0xFE,      // Return from the current function
+    Then = 0xFD,        // THEN <block: [ByteCode]> END Execute a block of code conditionally
     Else = 0xFC, // IF <block: [ByteCode]> ELSE <block: [ByteCode]> END Execute a block of code conditionally
     Loop = 0xFB, // LOOP <block: [ByteCode]> END Execute a block of code in a loop until instructed to break
     Break = 0xFA, // BREAK Exit the current loop
     Continue = 0xF9, // CONTINUE Skip to the next iteration of the current loop
+    Jump = 0xF8, // JUMP <target: u32> Continue at the instruction index target of the current function
+    JumpIfFalse = 0xF7, // JUMPIFNOT <target: u32> Pop a boolean and jump to target if it is false
+    Try = 0xF6, // TRY <block: [ByteCode]> CATCH <block: [ByteCode]> Run the catch block if the try block raises an error
+    Catch = 0xF5, // The handler of a TRY block, it starts with the thrown value on the stack
+    TryStart = 0xF4, // TRYSTART <handler: u32> Errors raised until the matching TRYEND continue at handler
+    TryEnd = 0xF3,   // TRYEND Remove the handler of the innermost TRYSTART
+    Throw = 0xF2,    // THROW Pop a value and raise it as an error
 }
 
 impl ByteCode {
@@ -73,12 +157,17 @@ impl ByteCode {
             0x17 => Some(ByteCode::Version),
             0x01 => Some(ByteCode::Dump),
             0x02 => Some(ByteCode::Hi),
+            0x20 => Some(ByteCode::Line),
             0x03 => Some(ByteCode::Func),
             0x04 => Some(ByteCode::Call),
+            0x21 => Some(ByteCode::Params),
+            0x22 => Some(ByteCode::Invoke),
             0x40 => Some(ByteCode::PushConstString),
             0x41 => Some(ByteCode::PushConstInteger),
             0x42 => Some(ByteCode::PushConstFloat),
             0x43 => Some(ByteCode::PushConstBoolean),
+            0x44 => Some(ByteCode::PushConstLong),
+            0x45 => Some(ByteCode::PushConstDouble),
             0x09 => Some(ByteCode::GetLocal),
             0x0A => Some(ByteCode::SetLocal),
             0x18 => Some(ByteCode::ReserveLocal),
@@ -93,6 +182,57 @@ impl ByteCode {
             0x10 => Some(ByteCode::Div),
             0x1D => Some(ByteCode::Inc),
             0x1E => Some(ByteCode::Dec),
+            0x27 => Some(ByteCode::Rem),
+            0x28 => Some(ByteCode::Mod),
+            0x29 => Some(ByteCode::Neg),
+            0x2A => Some(ByteCode::And),
+            0x2B => Some(ByteCode::Or),
+            0x2C => Some(ByteCode::Xor),
+            0x2D => Some(ByteCode::Not),
+            0x2E => Some(ByteCode::Shl),
+            0x2F => Some(ByteCode::Shr),
+            0x30 => Some(ByteCode::BoolNot),
+            0x31 => Some(ByteCode::ListNew),
+            0x32 => Some(ByteCode::ListPush),
+            0x33 => Some(ByteCode::ListPop),
+            0x34 => Some(ByteCode::ListLen),
+            0x35 => Some(ByteCode::ListGet),
+            0x36 => Some(ByteCode::ListSet),
+            0x37 => Some(ByteCode::MapNew),
+            0x38 => Some(ByteCode::MapInsert),
+            0x39 => Some(ByteCode::MapGet),
+            0x3A => Some(ByteCode::MapRemove),
+            0x3B => Some(ByteCode::MapContains),
+            0x3C => Some(ByteCode::MapLen),
+            0x3D => Some(ByteCode::MapKeys),
+            0x46 => Some(ByteCode::StringLength),
+            0x47 => Some(ByteCode::StringSubstring),
+            0x48 => Some(ByteCode::StringIndexOf),
+            0x49 => Some(ByteCode::StringSplit),
+            0x4A => Some(ByteCode::StringTrim),
+            0x4B => Some(ByteCode::StringUpper),
+            0x4C => Some(ByteCode::StringLower),
+            0x4D => Some(ByteCode::StringCharCode),
+            0x4E => Some(ByteCode::StringFromCharCode),
+            0x4F => Some(ByteCode::StringRepeat),
+            0x50 => Some(ByteCode::ToString),
+            0x51 => Some(ByteCode::ToInteger),
+            0x52 => Some(ByteCode::ToFloat),
+            0x53 => Some(ByteCode::TypeOf),
+            0x54 => Some(ByteCode::IsNull),
+            0x55 => Some(ByteCode::FunctionRef),
+            0x56 => Some(ByteCode::GetUpvalue),
+            0x57 => Some(ByteCode::CallIndirect),
+            0x58 => Some(ByteCode::Global),
+            0x59 => Some(ByteCode::GetGlobal),
+            0x5A => Some(ByteCode::SetGlobal),
+            0x5B => Some(ByteCode::GetProperty),
+            0x5C => Some(ByteCode::SetProperty),
+            0x5D => Some(ByteCode::CallMethod),
+            0x23 => Some(ByteCode::ConvertInteger),
+            0x24 => Some(ByteCode::ConvertLong),
+            0x25 => Some(ByteCode::ConvertFloat),
+            0x26 => Some(ByteCode::ConvertDouble),
             0x11 => Some(ByteCode::Eq),
             0x12 => Some(ByteCode::Ne),
             0x13 => Some(ByteCode::Lt),
@@ -100,6 +240,7 @@ impl ByteCode {
             0x15 => Some(ByteCode::Gt),
             0x16 => Some(ByteCode::Ge),
             0x1B => Some(ByteCode::Module),
+            0x1F => Some(ByteCode::Struct),
             0x19 => Some(ByteCode::LoadModule),
             0x1A => Some(ByteCode::GetFunction),
             0x1C => Some(ByteCode::Alias),
@@ -108,6 +249,14 @@ impl ByteCode {
             0xFC => Some(ByteCode::Else),
             0xFB => Some(ByteCode::Loop),
             0xFA => Some(ByteCode::Break),
+            0xF9 => Some(ByteCode::Continue),
+            0xF8 => Some(ByteCode::Jump),
+            0xF7 => Some(ByteCode::JumpIfFalse),
+            0xF6 => Some(ByteCode::Try),
+            0xF5 => Some(ByteCode::Catch),
+            0xF4 => Some(ByteCode::TryStart),
+            0xF3 => Some(ByteCode::TryEnd),
+            0xF2 => Some(ByteCode::Throw),
             _ => None,
         }
     }
@@ -0,0 +1,113 @@
+use crate::{ErrorKind, Instruction, Value, VirtualMachine};
+
+fn cannot_convert(value: &Value, to: &str) -> ErrorKind {
+    ErrorKind::InvalidArgument(format!("Cannot convert {:?} to {}", value, to))
+}
+
+// Unlike (conv.i32), values that don't fit an int are an error rather than wrapped
+pub(crate) fn to_int(value: &Value) -> Result<i32, ErrorKind> {
+    let converted = match value {
+        Value::Integer(i) => Some(*i),
+        Value::Long(l) => i32::try_from(*l).ok(),
+        Value::Float(f) => float_to_int(*f as f64),
+        Value::Double(d) => float_to_int(*d),
+        Value::Boolean(b) => Some(*b as i32),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Null | Value::Object(_) => {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "number, bool or string",
+                found: value.type_name(),
+            })
+        }
+    };
+
+    converted.ok_or_else(|| cannot_convert(value, "int"))
+}
+
+// Rounds toward zero, NaN and values out of range can't be converted
+fn float_to_int(value: f64) -> Option<i32> {
+    let value = value.trunc();
+
+    if value >= i32::MIN as f64 && value <= i32::MAX as f64 {
+        Some(value as i32)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn to_float(value: &Value) -> Result<f32, ErrorKind> {
+    match value {
+        Value::Integer(i) => Ok(*i as f32),
+        Value::Long(l) => Ok(*l as f32),
+        Value::Float(f) => Ok(*f),
+        Value::Double(d) => Ok(*d as f32),
+        Value::Boolean(b) => Ok(*b as i32 as f32),
+        Value::String(s) => s.trim().parse().map_err(|_| cannot_convert(value, "float")),
+        Value::Null | Value::Object(_) => Err(ErrorKind::TypeMismatch {
+            expected: "number, bool or string",
+            found: value.type_name(),
+        }),
+    }
+}
+
+impl VirtualMachine {
+    // Name of the type of a value, telling apart the kinds of heap objects
+    pub fn type_of(&self, value: &Value) -> &'static str {
+        match value {
+            Value::Object(handle) => self
+                .heap
+                .get(*handle)
+                .map_or(value.type_name(), |object| object.type_name()),
+            _ => value.type_name(),
+        }
+    }
+
+    // Execute (to.string), (to.int), (to.float), (type.of) or (is.null)
+    pub(crate) fn conversion(&mut self, instruction: &Instruction) -> Result<(), ErrorKind> {
+        let value = self.pop()?;
+
+        let value = match instruction {
+            Instruction::ToString => match value {
+                Value::String(_) => value,
+                Value::Null => Value::String("null".to_string()),
+                _ => Value::String(format!("{:?}", self.heap.debug(&value))),
+            },
+            Instruction::ToInteger => Value::Integer(to_int(&value)?),
+            Instruction::ToFloat => Value::Float(to_float(&value)?),
+            Instruction::TypeOf => Value::String(self.type_of(&value).to_string()),
+            Instruction::IsNull => Value::Boolean(matches!(value, Value::Null)),
+            _ => return Err(ErrorKind::InvalidInstruction),
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(to_int(&Value::String(" -42 ".to_string())), Ok(-42));
+        assert_eq!(to_int(&Value::Double(-2.9)), Ok(-2));
+        assert_eq!(to_int(&Value::Boolean(true)), Ok(1));
+        assert!(matches!(
+            to_int(&Value::String("4x".to_string())),
+            Err(ErrorKind::InvalidArgument(message)) if message == "Cannot convert \"4x\" to int"
+        ));
+        assert!(to_int(&Value::Long(1 << 40)).is_err());
+        assert!(to_int(&Value::Float(f32::NAN)).is_err());
+        assert_eq!(
+            to_int(&Value::Null),
+            Err(ErrorKind::TypeMismatch {
+                expected: "number, bool or string",
+                found: "null"
+            })
+        );
+        assert_eq!(to_float(&Value::String("1.5".to_string())), Ok(1.5));
+        assert_eq!(to_float(&Value::Long(3)), Ok(3.0));
+    }
+}
@@ -1,11 +1,212 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use libloading::Library;
 
-use crate::Value;
+use crate::{Code, Instruction, NativeApi, NativeFn, NativeRegistry, ABI_VERSION, API};
+
+// Environment variable listing directories to search for native modules, separated
+// like PATH
+pub const MODULE_PATH_VAR: &str = "MINTSCRIPT_PATH";
+
+// Directories native modules are searched in: `dirs` in order, then the ones in
+// MINTSCRIPT_PATH, the directory of the script and finally the current directory
+pub fn search_paths(dirs: &[PathBuf], script: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = dirs.to_vec();
+
+    if let Some(var) = std::env::var_os(MODULE_PATH_VAR) {
+        paths.extend(std::env::split_paths(&var).filter(|dir| !dir.as_os_str().is_empty()));
+    }
+
+    let script_dir = script
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty());
+
+    if let Some(dir) = script_dir {
+        paths.push(dir.to_path_buf());
+    }
+
+    paths.push(PathBuf::from("."));
+
+    paths
+}
+
+// File names the native module `name` may have on this platform, `std.mod` is looked
+// up as `libstd.mod.so` and `std.mod.so` before `std.mod` itself on Linux
+fn library_names(name: &str) -> Vec<String> {
+    if name.ends_with(DLL_SUFFIX) {
+        return vec![name.to_string()];
+    }
+
+    let mut names = vec![];
+
+    if !DLL_PREFIX.is_empty() && !name.starts_with(DLL_PREFIX) {
+        names.push(format!("{}{}{}", DLL_PREFIX, name, DLL_SUFFIX));
+    }
+
+    names.push(format!("{}{}", name, DLL_SUFFIX));
+    names.push(name.to_string());
+
+    names
+}
+
+// Path of the first existing file for the native module `name`. Names with a
+// directory, e.g. `./plugins/math`, are resolved against it rather than `search_paths`
+pub fn resolve_library(name: &str, search_paths: &[PathBuf]) -> Result<PathBuf, String> {
+    let path = Path::new(name);
+
+    let (dirs, file) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(file)) if !dir.as_os_str().is_empty() => {
+            (vec![dir.to_path_buf()], file.to_string_lossy())
+        }
+        _ => (search_paths.to_vec(), name.into()),
+    };
+
+    let mut tried = vec![];
+
+    for dir in dirs.iter() {
+        for file in library_names(&file) {
+            let candidate = dir.join(file);
+
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+
+            tried.push(candidate);
+        }
+    }
+
+    let mut message = format!("Native module {} not found, tried:", name);
+
+    for path in tried {
+        message.push_str(&format!("\n  {}", path.display()));
+    }
+
+    Err(message)
+}
 
 pub struct DyModule {
     pub name: String,
-    pub lib: Library,
-    pub fns: HashMap<String, Box<fn(Vec<Value>) -> Option<Value>>>,
+    // Shared with the calls linked to its functions, which keep it loaded
+    pub lib: Rc<Library>,
+    pub fns: HashMap<String, NativeFn>,
+}
+
+impl DyModule {
+    // Load the native plugin `name` for a (mod.load) and take the functions its (fn.get)
+    // instructions ask for from the ones it registers
+    pub fn load(name: &str, code: &Code, search_paths: &[PathBuf]) -> Result<DyModule, String> {
+        let path = resolve_library(name, search_paths)?;
+
+        let lib = unsafe { Library::new(&path).map_err(|e| e.to_string())? };
+
+        let version = unsafe {
+            let abi_version = lib
+                .get::<unsafe extern "C" fn() -> u32>(b"ms_plugin_abi_version")
+                .map_err(|_| format!("{}: Not a plugin, ms_plugin_abi_version is missing", name))?;
+
+            abi_version()
+        };
+
+        if version != ABI_VERSION {
+            return Err(format!(
+                "{}: Plugin ABI version {} is not supported, expected {}",
+                name, version, ABI_VERSION
+            ));
+        }
+
+        let mut registry = NativeRegistry::default();
+
+        let status = unsafe {
+            let init = lib
+                .get::<unsafe extern "C" fn(*const NativeApi, *mut NativeRegistry) -> i32>(
+                    b"ms_plugin_init",
+                )
+                .map_err(|_| format!("{}: Not a plugin, ms_plugin_init is missing", name))?;
+
+            init(&API, &mut registry)
+        };
+
+        if status != 0 {
+            return Err(format!(
+                "{}: Plugin initialization failed with status {}",
+                name, status
+            ));
+        }
+
+        let mut fns = HashMap::new();
+
+        for instruction in code.iter() {
+            let Instruction::GetFunction {
+                name: function,
+                alias,
+            } = instruction
+            else {
+                return Err("Invalid instruction type, expected (fn.get)".to_string());
+            };
+
+            let Some(native) = registry.fns.get(function) else {
+                return Err(format!(
+                    "{}: Function {} is not registered by the plugin",
+                    name, function
+                ));
+            };
+
+            fns.insert(alias.as_ref().unwrap_or(function).clone(), *native);
+        }
+
+        Ok(DyModule {
+            name: name.to_string(),
+            lib: Rc::new(lib),
+            fns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_library_names() {
+        let dir = std::env::temp_dir().join(format!("ms-resolve-{}", std::process::id()));
+        let empty = dir.join("empty");
+        std::fs::create_dir_all(&empty).unwrap();
+
+        let suffixed = dir.join(format!("util{}", DLL_SUFFIX));
+        std::fs::write(&suffixed, "").unwrap();
+        std::fs::write(dir.join("util"), "").unwrap();
+
+        let paths = vec![empty.clone(), dir.clone()];
+
+        // The platform file name wins over the bare name, earlier directories over later ones
+        assert_eq!(resolve_library("util", &paths).unwrap(), suffixed);
+        assert_eq!(
+            resolve_library(&format!("util{}", DLL_SUFFIX), &paths).unwrap(),
+            suffixed
+        );
+
+        // Names with a directory ignore the search paths
+        let with_dir = format!("{}", dir.join("util").display());
+        assert_eq!(resolve_library(&with_dir, &[]).unwrap(), suffixed);
+
+        let error = resolve_library("missing", &paths).unwrap_err();
+        let tried: Vec<_> = error.lines().skip(1).map(str::trim).collect();
+
+        assert!(error.starts_with("Native module missing not found, tried:"));
+        assert_eq!(tried.len(), library_names("missing").len() * 2);
+        assert_eq!(
+            tried[0],
+            empty
+                .join(&library_names("missing")[0])
+                .display()
+                .to_string()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+// A function that was being executed when an error was raised, one entry of its stack trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub module: String,
+    pub function: String,
+    // Index of the instruction being executed in the function's lowered code
+    pub instruction: usize,
+    // Source line from the (line) debug info, if the code has any
+    pub line: Option<u32>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}, instruction {}",
+            self.module, self.function, self.instruction
+        )?;
+
+        if let Some(line) = self.line {
+            write!(f, ", line {}", line)?;
+        }
+
+        Ok(())
+    }
+}
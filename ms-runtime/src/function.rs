@@ -1,6 +1,30 @@
-use crate::instruction::Code;
+use crate::{
+    instruction::{Code, Instruction},
+    lower,
+};
 
 pub struct Function {
+    // Module the function was loaded into
+    pub module: String,
+    // Name the function is called by, `Struct.function` for struct functions
     pub name: String,
+    // Parameter count from a leading (fn.params), None when the function doesn't declare it
+    pub params: Option<u32>,
     pub code: Code,
 }
+
+impl Function {
+    pub fn new(module: &str, name: &str, code: &Code) -> Function {
+        let params = match code.first() {
+            Some(Instruction::Params { count }) => Some(*count),
+            _ => None,
+        };
+
+        Function {
+            module: module.to_string(),
+            name: name.to_string(),
+            params,
+            code: lower(code),
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    fmt::{Debug, Display},
+    time::{Duration, Instant},
+};
+
+use crate::{Object, Value};
+
+// Address of an object in the VM's heap. The generation tells apart the objects
+// that reuse a slot, so a handle kept past its object's collection is detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Display for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.index)
+    }
+}
+
+struct Slot {
+    generation: u32,
+    object: Option<Object>,
+    marked: bool,
+}
+
+// Collections start once this many objects are live, unless configured otherwise
+const DEFAULT_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub allocated: usize,
+    pub freed: usize,
+    pub live: usize,
+    pub peak: usize,
+    pub time: Duration,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "GC collections: {}", self.collections)?;
+        writeln!(f, "GC time: {:?}", self.time)?;
+        writeln!(f, "Objects allocated: {}", self.allocated)?;
+        writeln!(f, "Objects freed: {}", self.freed)?;
+        writeln!(f, "Objects live: {}", self.live)?;
+        write!(f, "Objects peak: {}", self.peak)
+    }
+}
+
+// Objects owned by the VM. Values refer to them by handle and unreachable objects,
+// cycles included, are reclaimed by a mark and sweep collection from the VM's roots
+pub struct Heap {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    // Number of live objects the heap may hold, allocations past it fail
+    pub limit: Option<usize>,
+    // Minimum number of live objects before a collection is triggered
+    pub threshold: usize,
+    next_collection: usize,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            slots: Vec::new(),
+            free: Vec::new(),
+            limit: None,
+            threshold: DEFAULT_THRESHOLD,
+            next_collection: DEFAULT_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stats.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.live == 0
+    }
+
+    pub fn stats(&self) -> &GcStats {
+        &self.stats
+    }
+
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold;
+        self.next_collection = threshold;
+    }
+
+    // Whether the next allocation should be preceded by a collection
+    pub fn should_collect(&self) -> bool {
+        self.stats.live >= self.next_collection || self.is_full()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.stats.live >= limit)
+    }
+
+    // Store an object, fails when the heap limit is reached
+    pub fn allocate(&mut self, object: Object) -> Option<Handle> {
+        if self.is_full() {
+            return None;
+        }
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].object = Some(object);
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    object: Some(object),
+                    marked: false,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+
+        self.stats.allocated += 1;
+        self.stats.live += 1;
+        self.stats.peak = self.stats.peak.max(self.stats.live);
+
+        Some(Handle {
+            index,
+            generation: self.slots[index as usize].generation,
+        })
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&Object> {
+        let slot = self.slots.get(handle.index as usize)?;
+
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        slot.object.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut Object> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        slot.object.as_mut()
+    }
+
+    // Free every object not reachable from `roots`, returns the number of objects freed
+    pub fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a Value>) -> usize {
+        let start = Instant::now();
+
+        let mut pending: Vec<Handle> = roots.filter_map(Value::handle).collect();
+
+        while let Some(handle) = pending.pop() {
+            let Some(slot) = self.slots.get_mut(handle.index as usize) else {
+                continue;
+            };
+
+            if slot.marked || slot.generation != handle.generation {
+                continue;
+            }
+
+            slot.marked = true;
+
+            match &slot.object {
+                Some(Object::Values(values) | Object::List(values)) => {
+                    pending.extend(values.iter().filter_map(Value::handle));
+                }
+                Some(Object::Map(entries)) => {
+                    pending.extend(entries.values().filter_map(|(_, value)| value.handle()));
+                }
+                Some(Object::Function(closure)) => {
+                    pending.extend(closure.upvalues.iter().filter_map(Value::handle));
+                }
+                Some(Object::Native(native)) => {
+                    pending.extend(native.trace().iter().filter_map(Value::handle));
+                }
+                _ => {}
+            }
+        }
+
+        let mut freed = 0;
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.marked {
+                slot.marked = false;
+            } else if let Some(object) = slot.object.take() {
+                if let Object::Native(mut native) = object {
+                    native.finalize();
+                }
+
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+                freed += 1;
+            }
+        }
+
+        self.stats.collections += 1;
+        self.stats.freed += freed;
+        self.stats.live -= freed;
+        self.stats.time += start.elapsed();
+
+        // Grow with the live set so collections stay proportional to the allocations
+        self.next_collection = self.threshold.max(self.stats.live * 2);
+
+        freed
+    }
+
+    // Debug formatting that follows handles into the heap, `Object[1, 2]` for objects,
+    // `[1, 2]` for lists, `{"a": 1}` for maps and `fn main.add` for functions
+    pub fn debug<'a>(&'a self, value: &'a Value) -> impl Debug + 'a {
+        HeapValue {
+            heap: self,
+            value,
+            depth: 0,
+        }
+    }
+}
+
+// Natives still alive when the heap goes away are finalized too
+impl Drop for Heap {
+    fn drop(&mut self) {
+        for slot in &mut self.slots {
+            if let Some(Object::Native(native)) = &mut slot.object {
+                native.finalize();
+            }
+        }
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Nesting past which objects are printed as their handle, cyclic objects would
+// be printed forever otherwise
+const MAX_DEBUG_DEPTH: usize = 16;
+
+struct HeapValue<'a> {
+    heap: &'a Heap,
+    value: &'a Value,
+    depth: usize,
+}
+
+impl Debug for HeapValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Value::Object(handle) = self.value else {
+            return write!(f, "{:?}", self.value);
+        };
+
+        if self.depth >= MAX_DEBUG_DEPTH {
+            return write!(f, "{:?}", self.value);
+        }
+
+        let values = match self.heap.get(*handle) {
+            Some(Object::Values(values)) => {
+                write!(f, "Object")?;
+                values
+            }
+            Some(Object::List(values)) => values,
+            Some(Object::Map(entries)) => {
+                let child = |value| HeapValue {
+                    heap: self.heap,
+                    value,
+                    depth: self.depth + 1,
+                };
+
+                return f
+                    .debug_map()
+                    .entries(
+                        entries
+                            .values()
+                            .map(|(key, value)| (child(key), child(value))),
+                    )
+                    .finish();
+            }
+            Some(Object::Function(closure)) => {
+                return write!(f, "fn {}.{}", closure.module, closure.name)
+            }
+            Some(Object::Native(native)) => return write!(f, "{}", native.type_name()),
+            None => return write!(f, "{:?}", self.value),
+        };
+
+        write!(f, "[")?;
+
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            let value = HeapValue {
+                heap: self.heap,
+                value,
+                depth: self.depth + 1,
+            };
+
+            write!(f, "{:?}", value)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heap_collects_cycles() {
+        let mut heap = Heap::new();
+
+        let a = heap.allocate(Object::Values(vec![Value::Null])).unwrap();
+        let b = heap
+            .allocate(Object::Values(vec![Value::Object(a)]))
+            .unwrap();
+
+        // a <-> b
+        let Some(Object::Values(fields)) = heap.get_mut(a) else {
+            unreachable!()
+        };
+        fields[0] = Value::Object(b);
+
+        let kept = heap.allocate(Object::Values(vec![])).unwrap();
+
+        assert_eq!(heap.collect([Value::Object(kept)].iter()), 2);
+        assert_eq!(heap.len(), 1);
+        assert!(heap.get(a).is_none());
+        assert!(heap.get(kept).is_some());
+
+        // The freed slot is reused without reviving the stale handle
+        let c = heap.allocate(Object::Values(vec![])).unwrap();
+        assert!(heap.get(c).is_some());
+        assert!(heap.get(a).is_none() && heap.get(b).is_none());
+
+        let stats = heap.stats();
+        assert_eq!((stats.collections, stats.freed, stats.peak), (1, 2, 3));
+    }
+
+    #[test]
+    fn heap_limit() {
+        let mut heap = Heap::new();
+        heap.limit = Some(1);
+
+        let a = heap.allocate(Object::Values(vec![])).unwrap();
+        assert!(heap.allocate(Object::Values(vec![])).is_none());
+
+        assert_eq!(heap.collect(std::iter::empty()), 1);
+        assert!(heap.allocate(Object::Values(vec![])).is_some());
+        assert!(heap.get(a).is_none());
+    }
+}
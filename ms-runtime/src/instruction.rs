@@ -15,6 +15,10 @@ pub enum Instruction {
     // Debugging
     Dump,
     Hi,
+    // Source line of the instructions that follow, used for stack traces
+    Line {
+        line: u32,
+    },
 
     // Functions
     Fn {
@@ -26,6 +30,15 @@ pub enum Instruction {
         function: String,
         param_count: u32,
     },
+    // Number of parameters of the function, its first instruction when declared
+    Params {
+        count: u32,
+    },
+    // A call resolved by `VirtualMachine::link` to an index in the VM's function table
+    Invoke {
+        index: u32,
+        param_count: u32,
+    },
 
     // Constants
     PushConstString {
@@ -37,6 +50,12 @@ pub enum Instruction {
     PushConstFloat {
         value: f32,
     },
+    PushConstLong {
+        value: i64,
+    },
+    PushConstDouble {
+        value: f64,
+    },
     PushConstBoolean {
         value: bool,
     },
@@ -74,6 +93,77 @@ pub enum Instruction {
     Div,
     Inc,
     Dec,
+    Rem,
+    Mod,
+    Neg,
+
+    // Bitwise and logical
+    And,
+    Or,
+    Xor,
+    Not,
+    Shl,
+    Shr,
+    BoolNot,
+
+    // Lists
+    ListNew,
+    ListPush,
+    ListPop,
+    ListLen,
+    ListGet,
+    ListSet,
+
+    // Maps
+    MapNew,
+    MapInsert,
+    MapGet,
+    MapRemove,
+    MapContains,
+    MapLen,
+    MapKeys,
+
+    // Strings
+    StringLength,
+    StringSubstring,
+    StringIndexOf,
+    StringSplit,
+    StringTrim,
+    StringUpper,
+    StringLower,
+    StringCharCode,
+    StringFromCharCode,
+    StringRepeat,
+
+    // Conversion and introspection
+    ToString,
+    ToInteger,
+    ToFloat,
+    TypeOf,
+    IsNull,
+
+    // Function values
+    // Push a function value, the closure of `function` over `captures` values popped
+    // from the stack
+    FunctionRef {
+        module: String,
+        function: String,
+        captures: u32,
+    },
+    // Push a value captured by the closure being executed
+    GetUpvalue {
+        index: u32,
+    },
+    // Call the function value below the arguments
+    CallIndirect {
+        param_count: u32,
+    },
+
+    // Numeric conversions
+    ConvertInteger,
+    ConvertLong,
+    ConvertFloat,
+    ConvertDouble,
 
     // Comparison
     Eq,
@@ -89,6 +179,40 @@ pub enum Instruction {
         code: Code,
     },
 
+    // Structs
+    Struct {
+        name: String,
+        code: Code,
+    },
+
+    // Globals
+    // A variable of the module that keeps its value between calls, `init` is a single
+    // constant instruction or empty for null
+    Global {
+        name: String,
+        init: Code,
+    },
+    // Access a global of the module of the running function
+    GetGlobal {
+        name: String,
+    },
+    SetGlobal {
+        name: String,
+    },
+
+    // Native objects
+    GetProperty {
+        name: String,
+    },
+    SetProperty {
+        name: String,
+    },
+    // Call a method of the native object below the arguments
+    CallMethod {
+        name: String,
+        param_count: u32,
+    },
+
     // Dynamic Module
     LoadModule {
         name: String,
@@ -110,6 +234,26 @@ pub enum Instruction {
     },
     Break,
     Continue,
+    // Flat control flow produced by `lower`, targets index into the function's code
+    Jump {
+        target: u32,
+    },
+    JumpIfFalse {
+        target: u32,
+    },
+    // Errors raised while running `try_block` run `catch_block` with the thrown value
+    // on the stack instead
+    Try {
+        try_block: Code,
+        catch_block: Code,
+    },
+    // Flat (try) blocks produced by `lower`, errors raised until the matching (try.end)
+    // continue at `handler`
+    TryStart {
+        handler: u32,
+    },
+    TryEnd,
+    Throw,
 }
 
 impl PartialEq<Self> for Instruction {
@@ -130,6 +274,18 @@ impl PartialEq<Self> for Instruction {
             ) => true,
             (Instruction::Dump, Instruction::Dump) => true,
             (Instruction::Hi, Instruction::Hi) => true,
+            (Instruction::Line { line: _a }, Instruction::Line { line: _x }) => true,
+            (Instruction::Params { count: _a }, Instruction::Params { count: _x }) => true,
+            (
+                Instruction::Invoke {
+                    index: _a,
+                    param_count: _b,
+                },
+                Instruction::Invoke {
+                    index: _x,
+                    param_count: _y,
+                },
+            ) => true,
             (Instruction::Fn { name: _a, code: _b }, Instruction::Fn { name: _x, code: _y }) => {
                 true
             }
@@ -157,6 +313,14 @@ impl PartialEq<Self> for Instruction {
                 Instruction::PushConstFloat { value: _a },
                 Instruction::PushConstFloat { value: _x },
             ) => true,
+            (
+                Instruction::PushConstLong { value: _a },
+                Instruction::PushConstLong { value: _x },
+            ) => true,
+            (
+                Instruction::PushConstDouble { value: _a },
+                Instruction::PushConstDouble { value: _x },
+            ) => true,
             (
                 Instruction::PushConstBoolean { value: _a },
                 Instruction::PushConstBoolean { value: _x },
@@ -177,6 +341,57 @@ impl PartialEq<Self> for Instruction {
             (Instruction::Div, Instruction::Div) => true,
             (Instruction::Inc, Instruction::Inc) => true,
             (Instruction::Dec, Instruction::Dec) => true,
+            (Instruction::Rem, Instruction::Rem) => true,
+            (Instruction::Mod, Instruction::Mod) => true,
+            (Instruction::Neg, Instruction::Neg) => true,
+            (Instruction::And, Instruction::And) => true,
+            (Instruction::Or, Instruction::Or) => true,
+            (Instruction::Xor, Instruction::Xor) => true,
+            (Instruction::Not, Instruction::Not) => true,
+            (Instruction::Shl, Instruction::Shl) => true,
+            (Instruction::Shr, Instruction::Shr) => true,
+            (Instruction::BoolNot, Instruction::BoolNot) => true,
+            (Instruction::ListNew, Instruction::ListNew) => true,
+            (Instruction::ListPush, Instruction::ListPush) => true,
+            (Instruction::ListPop, Instruction::ListPop) => true,
+            (Instruction::ListLen, Instruction::ListLen) => true,
+            (Instruction::ListGet, Instruction::ListGet) => true,
+            (Instruction::ListSet, Instruction::ListSet) => true,
+            (Instruction::MapNew, Instruction::MapNew) => true,
+            (Instruction::MapInsert, Instruction::MapInsert) => true,
+            (Instruction::MapGet, Instruction::MapGet) => true,
+            (Instruction::MapRemove, Instruction::MapRemove) => true,
+            (Instruction::MapContains, Instruction::MapContains) => true,
+            (Instruction::MapLen, Instruction::MapLen) => true,
+            (Instruction::MapKeys, Instruction::MapKeys) => true,
+            (Instruction::StringLength, Instruction::StringLength) => true,
+            (Instruction::StringSubstring, Instruction::StringSubstring) => true,
+            (Instruction::StringIndexOf, Instruction::StringIndexOf) => true,
+            (Instruction::StringSplit, Instruction::StringSplit) => true,
+            (Instruction::StringTrim, Instruction::StringTrim) => true,
+            (Instruction::StringUpper, Instruction::StringUpper) => true,
+            (Instruction::StringLower, Instruction::StringLower) => true,
+            (Instruction::StringCharCode, Instruction::StringCharCode) => true,
+            (Instruction::StringFromCharCode, Instruction::StringFromCharCode) => true,
+            (Instruction::StringRepeat, Instruction::StringRepeat) => true,
+            (Instruction::ToString, Instruction::ToString) => true,
+            (Instruction::ToInteger, Instruction::ToInteger) => true,
+            (Instruction::ToFloat, Instruction::ToFloat) => true,
+            (Instruction::TypeOf, Instruction::TypeOf) => true,
+            (Instruction::IsNull, Instruction::IsNull) => true,
+            (Instruction::FunctionRef { .. }, Instruction::FunctionRef { .. }) => true,
+            (Instruction::GetUpvalue { .. }, Instruction::GetUpvalue { .. }) => true,
+            (Instruction::CallIndirect { .. }, Instruction::CallIndirect { .. }) => true,
+            (Instruction::Global { .. }, Instruction::Global { .. }) => true,
+            (Instruction::GetGlobal { .. }, Instruction::GetGlobal { .. }) => true,
+            (Instruction::SetGlobal { .. }, Instruction::SetGlobal { .. }) => true,
+            (Instruction::GetProperty { .. }, Instruction::GetProperty { .. }) => true,
+            (Instruction::SetProperty { .. }, Instruction::SetProperty { .. }) => true,
+            (Instruction::CallMethod { .. }, Instruction::CallMethod { .. }) => true,
+            (Instruction::ConvertInteger, Instruction::ConvertInteger) => true,
+            (Instruction::ConvertLong, Instruction::ConvertLong) => true,
+            (Instruction::ConvertFloat, Instruction::ConvertFloat) => true,
+            (Instruction::ConvertDouble, Instruction::ConvertDouble) => true,
             (Instruction::Eq, Instruction::Eq) => true,
             (Instruction::Ne, Instruction::Ne) => true,
             (Instruction::Lt, Instruction::Lt) => true,
@@ -187,6 +402,10 @@ impl PartialEq<Self> for Instruction {
                 Instruction::Module { name: _a, code: _b },
                 Instruction::Module { name: _x, code: _y },
             ) => true,
+            (
+                Instruction::Struct { name: _a, code: _b },
+                Instruction::Struct { name: _x, code: _y },
+            ) => true,
             (
                 Instruction::LoadModule { name: _a, code: _b },
                 Instruction::LoadModule { name: _x, code: _y },
@@ -215,6 +434,14 @@ impl PartialEq<Self> for Instruction {
             (Instruction::Loop { block: _a }, Instruction::Loop { block: _x }) => true,
             (Instruction::Break, Instruction::Break) => true,
             (Instruction::Continue, Instruction::Continue) => true,
+            (Instruction::Jump { target: _a }, Instruction::Jump { target: _x }) => true,
+            (Instruction::JumpIfFalse { target: _a }, Instruction::JumpIfFalse { target: _x }) => {
+                true
+            }
+            (Instruction::Try { .. }, Instruction::Try { .. }) => true,
+            (Instruction::TryStart { .. }, Instruction::TryStart { .. }) => true,
+            (Instruction::TryEnd, Instruction::TryEnd) => true,
+            (Instruction::Throw, Instruction::Throw) => true,
             _ => false,
         }
     }
@@ -274,6 +501,72 @@ impl Hash for Instruction {
             Instruction::Loop { block: _ } => 35.hash(state),
             Instruction::Break => 36.hash(state),
             Instruction::Continue => 37.hash(state),
+            Instruction::Struct { name: _, code: _ } => 38.hash(state),
+            Instruction::Line { line: _ } => 39.hash(state),
+            Instruction::Jump { target: _ } => 40.hash(state),
+            Instruction::JumpIfFalse { target: _ } => 41.hash(state),
+            Instruction::Params { count: _ } => 42.hash(state),
+            Instruction::Invoke {
+                index: _,
+                param_count: _,
+            } => 43.hash(state),
+            Instruction::PushConstLong { value: _ } => 44.hash(state),
+            Instruction::PushConstDouble { value: _ } => 45.hash(state),
+            Instruction::ConvertInteger => 46.hash(state),
+            Instruction::ConvertLong => 47.hash(state),
+            Instruction::ConvertFloat => 48.hash(state),
+            Instruction::ConvertDouble => 49.hash(state),
+            Instruction::Rem => 50.hash(state),
+            Instruction::Mod => 51.hash(state),
+            Instruction::Neg => 52.hash(state),
+            Instruction::And => 53.hash(state),
+            Instruction::Or => 54.hash(state),
+            Instruction::Xor => 55.hash(state),
+            Instruction::Not => 56.hash(state),
+            Instruction::Shl => 57.hash(state),
+            Instruction::Shr => 58.hash(state),
+            Instruction::BoolNot => 59.hash(state),
+            Instruction::ListNew => 60.hash(state),
+            Instruction::ListPush => 61.hash(state),
+            Instruction::ListPop => 62.hash(state),
+            Instruction::ListLen => 63.hash(state),
+            Instruction::ListGet => 64.hash(state),
+            Instruction::ListSet => 65.hash(state),
+            Instruction::MapNew => 66.hash(state),
+            Instruction::MapInsert => 67.hash(state),
+            Instruction::MapGet => 68.hash(state),
+            Instruction::MapRemove => 69.hash(state),
+            Instruction::MapContains => 70.hash(state),
+            Instruction::MapLen => 71.hash(state),
+            Instruction::MapKeys => 72.hash(state),
+            Instruction::StringLength => 73.hash(state),
+            Instruction::StringSubstring => 74.hash(state),
+            Instruction::StringIndexOf => 75.hash(state),
+            Instruction::StringSplit => 76.hash(state),
+            Instruction::StringTrim => 77.hash(state),
+            Instruction::StringUpper => 78.hash(state),
+            Instruction::StringLower => 79.hash(state),
+            Instruction::StringCharCode => 80.hash(state),
+            Instruction::StringFromCharCode => 81.hash(state),
+            Instruction::StringRepeat => 82.hash(state),
+            Instruction::ToString => 83.hash(state),
+            Instruction::ToInteger => 84.hash(state),
+            Instruction::ToFloat => 85.hash(state),
+            Instruction::TypeOf => 86.hash(state),
+            Instruction::IsNull => 87.hash(state),
+            Instruction::FunctionRef { .. } => 88.hash(state),
+            Instruction::GetUpvalue { .. } => 89.hash(state),
+            Instruction::CallIndirect { .. } => 90.hash(state),
+            Instruction::Global { .. } => 91.hash(state),
+            Instruction::GetGlobal { .. } => 92.hash(state),
+            Instruction::SetGlobal { .. } => 93.hash(state),
+            Instruction::Try { .. } => 94.hash(state),
+            Instruction::TryStart { .. } => 95.hash(state),
+            Instruction::TryEnd => 96.hash(state),
+            Instruction::Throw => 97.hash(state),
+            Instruction::GetProperty { .. } => 98.hash(state),
+            Instruction::SetProperty { .. } => 99.hash(state),
+            Instruction::CallMethod { .. } => 100.hash(state),
         }
     }
 }
@@ -313,6 +606,13 @@ impl<'a> Instruction {
                 }
                 ByteCode::Dump => code.push(Instruction::Dump),
                 ByteCode::Hi => code.push(Instruction::Hi),
+                ByteCode::Line => {
+                    let Some(line) = reader.read_u32() else {
+                        return Err("Expected line number".to_string());
+                    };
+
+                    code.push(Instruction::Line { line });
+                }
                 ByteCode::Func => {
                     let Some(lenght) = reader.read_u32() else {
                         return Err("Expected function code length".to_string());
@@ -351,6 +651,24 @@ impl<'a> Instruction {
                         param_count,
                     });
                 }
+                ByteCode::Params => {
+                    let Some(count) = reader.read_u32() else {
+                        return Err("Expected parameter count".to_string());
+                    };
+
+                    code.push(Instruction::Params { count });
+                }
+                ByteCode::Invoke => {
+                    let Some(index) = reader.read_u32() else {
+                        return Err("Expected function index".to_string());
+                    };
+
+                    let Some(param_count) = reader.read_u32() else {
+                        return Err("Expected parameter count".to_string());
+                    };
+
+                    code.push(Instruction::Invoke { index, param_count });
+                }
                 ByteCode::PushConstString => {
                     let Some(value) = reader.read_string() else {
                         return Err("Expected string value".to_string());
@@ -372,6 +690,20 @@ impl<'a> Instruction {
 
                     code.push(Instruction::PushConstFloat { value: value });
                 }
+                ByteCode::PushConstLong => {
+                    let Some(value) = reader.read_i64() else {
+                        return Err("Expected long value".to_string());
+                    };
+
+                    code.push(Instruction::PushConstLong { value });
+                }
+                ByteCode::PushConstDouble => {
+                    let Some(value) = reader.read_f64() else {
+                        return Err("Expected double value".to_string());
+                    };
+
+                    code.push(Instruction::PushConstDouble { value });
+                }
                 ByteCode::PushConstBoolean => {
                     let Some(value) = reader.read_bool() else {
                         return Err("Expected boolean value".to_string());
@@ -429,6 +761,138 @@ impl<'a> Instruction {
                 ByteCode::Div => code.push(Instruction::Div),
                 ByteCode::Inc => code.push(Instruction::Inc),
                 ByteCode::Dec => code.push(Instruction::Dec),
+                ByteCode::Rem => code.push(Instruction::Rem),
+                ByteCode::Mod => code.push(Instruction::Mod),
+                ByteCode::Neg => code.push(Instruction::Neg),
+                ByteCode::And => code.push(Instruction::And),
+                ByteCode::Or => code.push(Instruction::Or),
+                ByteCode::Xor => code.push(Instruction::Xor),
+                ByteCode::Not => code.push(Instruction::Not),
+                ByteCode::Shl => code.push(Instruction::Shl),
+                ByteCode::Shr => code.push(Instruction::Shr),
+                ByteCode::BoolNot => code.push(Instruction::BoolNot),
+                ByteCode::ListNew => code.push(Instruction::ListNew),
+                ByteCode::ListPush => code.push(Instruction::ListPush),
+                ByteCode::ListPop => code.push(Instruction::ListPop),
+                ByteCode::ListLen => code.push(Instruction::ListLen),
+                ByteCode::ListGet => code.push(Instruction::ListGet),
+                ByteCode::ListSet => code.push(Instruction::ListSet),
+                ByteCode::MapNew => code.push(Instruction::MapNew),
+                ByteCode::MapInsert => code.push(Instruction::MapInsert),
+                ByteCode::MapGet => code.push(Instruction::MapGet),
+                ByteCode::MapRemove => code.push(Instruction::MapRemove),
+                ByteCode::MapContains => code.push(Instruction::MapContains),
+                ByteCode::MapLen => code.push(Instruction::MapLen),
+                ByteCode::MapKeys => code.push(Instruction::MapKeys),
+                ByteCode::StringLength => code.push(Instruction::StringLength),
+                ByteCode::StringSubstring => code.push(Instruction::StringSubstring),
+                ByteCode::StringIndexOf => code.push(Instruction::StringIndexOf),
+                ByteCode::StringSplit => code.push(Instruction::StringSplit),
+                ByteCode::StringTrim => code.push(Instruction::StringTrim),
+                ByteCode::StringUpper => code.push(Instruction::StringUpper),
+                ByteCode::StringLower => code.push(Instruction::StringLower),
+                ByteCode::StringCharCode => code.push(Instruction::StringCharCode),
+                ByteCode::StringFromCharCode => code.push(Instruction::StringFromCharCode),
+                ByteCode::StringRepeat => code.push(Instruction::StringRepeat),
+                ByteCode::ToString => code.push(Instruction::ToString),
+                ByteCode::ToInteger => code.push(Instruction::ToInteger),
+                ByteCode::ToFloat => code.push(Instruction::ToFloat),
+                ByteCode::TypeOf => code.push(Instruction::TypeOf),
+                ByteCode::IsNull => code.push(Instruction::IsNull),
+                ByteCode::FunctionRef => {
+                    let Some(module) = reader.read_string() else {
+                        return Err("Expected module name".to_string());
+                    };
+
+                    let Some(function) = reader.read_string() else {
+                        return Err("Expected function name".to_string());
+                    };
+
+                    let Some(captures) = reader.read_u32() else {
+                        return Err("Expected capture count".to_string());
+                    };
+
+                    code.push(Instruction::FunctionRef {
+                        module,
+                        function,
+                        captures,
+                    });
+                }
+                ByteCode::GetUpvalue => {
+                    let Some(index) = reader.read_u32() else {
+                        return Err("Expected upvalue index".to_string());
+                    };
+
+                    code.push(Instruction::GetUpvalue { index });
+                }
+                ByteCode::CallIndirect => {
+                    let Some(param_count) = reader.read_u32() else {
+                        return Err("Expected parameter count".to_string());
+                    };
+
+                    code.push(Instruction::CallIndirect { param_count });
+                }
+                ByteCode::Global => {
+                    let Some(lenght) = reader.read_u32() else {
+                        return Err("Expected global initializer length".to_string());
+                    };
+
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected global name".to_string());
+                    };
+
+                    let Some(init) = reader.read_bytes(lenght as usize) else {
+                        return Err("Expected global initializer".to_string());
+                    };
+
+                    code.push(Instruction::Global {
+                        name,
+                        init: Instruction::from_bytecode(&init)?,
+                    });
+                }
+                ByteCode::GetGlobal => {
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected global name".to_string());
+                    };
+
+                    code.push(Instruction::GetGlobal { name });
+                }
+                ByteCode::SetGlobal => {
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected global name".to_string());
+                    };
+
+                    code.push(Instruction::SetGlobal { name });
+                }
+                ByteCode::GetProperty => {
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected property name".to_string());
+                    };
+
+                    code.push(Instruction::GetProperty { name });
+                }
+                ByteCode::SetProperty => {
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected property name".to_string());
+                    };
+
+                    code.push(Instruction::SetProperty { name });
+                }
+                ByteCode::CallMethod => {
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected method name".to_string());
+                    };
+
+                    let Some(param_count) = reader.read_u32() else {
+                        return Err("Expected parameter count".to_string());
+                    };
+
+                    code.push(Instruction::CallMethod { name, param_count });
+                }
+                ByteCode::ConvertInteger => code.push(Instruction::ConvertInteger),
+                ByteCode::ConvertLong => code.push(Instruction::ConvertLong),
+                ByteCode::ConvertFloat => code.push(Instruction::ConvertFloat),
+                ByteCode::ConvertDouble => code.push(Instruction::ConvertDouble),
                 ByteCode::Eq => code.push(Instruction::Eq),
                 ByteCode::Ne => code.push(Instruction::Ne),
                 ByteCode::Lt => code.push(Instruction::Lt),
@@ -453,6 +917,24 @@ impl<'a> Instruction {
                         code: Instruction::from_bytecode(&module_code)?,
                     });
                 }
+                ByteCode::Struct => {
+                    let Some(lenght) = reader.read_u32() else {
+                        return Err("Expected struct code length".to_string());
+                    };
+
+                    let Some(name) = reader.read_string() else {
+                        return Err("Expected struct name".to_string());
+                    };
+
+                    let Some(struct_code) = reader.read_bytes(lenght as usize) else {
+                        return Err("Expected struct code".to_string());
+                    };
+
+                    code.push(Instruction::Struct {
+                        name,
+                        code: Instruction::from_bytecode(&struct_code)?,
+                    });
+                }
                 ByteCode::LoadModule => {
                     let Some(lenght) = reader.read_u32() else {
                         return Err("Expected module code length".to_string());
@@ -555,11 +1037,172 @@ impl<'a> Instruction {
                 }
                 ByteCode::Break => code.push(Instruction::Break),
                 ByteCode::Continue => code.push(Instruction::Continue),
+                ByteCode::Jump => {
+                    let Some(target) = reader.read_u32() else {
+                        return Err("Expected jump target".to_string());
+                    };
+
+                    code.push(Instruction::Jump { target });
+                }
+                ByteCode::JumpIfFalse => {
+                    let Some(target) = reader.read_u32() else {
+                        return Err("Expected jump target".to_string());
+                    };
+
+                    code.push(Instruction::JumpIfFalse { target });
+                }
+                ByteCode::Try => {
+                    let Some(lenght) = reader.read_u32() else {
+                        return Err("Expected block code length".to_string());
+                    };
+
+                    let Some(block) = reader.read_bytes(lenght as usize) else {
+                        return Err("Expected block code".to_string());
+                    };
+
+                    let try_block = Instruction::from_bytecode(&block)?;
+
+                    if reader.read_byte().and_then(ByteCode::from_u8) != Some(ByteCode::Catch) {
+                        return Err("Expected catch block".to_string());
+                    }
+
+                    let Some(lenght) = reader.read_u32() else {
+                        return Err("Expected block code length".to_string());
+                    };
+
+                    let Some(block) = reader.read_bytes(lenght as usize) else {
+                        return Err("Expected block code".to_string());
+                    };
+
+                    code.push(Instruction::Try {
+                        try_block,
+                        catch_block: Instruction::from_bytecode(&block)?,
+                    });
+                }
+                ByteCode::Catch => {
+                    return Err("Invalid instruction (catch) outside of try block".to_string());
+                }
+                ByteCode::TryStart => {
+                    let Some(handler) = reader.read_u32() else {
+                        return Err("Expected handler target".to_string());
+                    };
+
+                    code.push(Instruction::TryStart { handler });
+                }
+                ByteCode::TryEnd => code.push(Instruction::TryEnd),
+                ByteCode::Throw => code.push(Instruction::Throw),
             }
         }
         Ok(code)
     }
 
+    // Opcode the instruction is encoded with
+    pub fn bytecode(&self) -> ByteCode {
+        match self {
+            Instruction::None => ByteCode::None,
+            Instruction::Version { .. } => ByteCode::Version,
+            Instruction::Dump => ByteCode::Dump,
+            Instruction::Hi => ByteCode::Hi,
+            Instruction::Line { .. } => ByteCode::Line,
+            Instruction::Fn { .. } => ByteCode::Func,
+            Instruction::Call { .. } => ByteCode::Call,
+            Instruction::Params { .. } => ByteCode::Params,
+            Instruction::Invoke { .. } => ByteCode::Invoke,
+            Instruction::PushConstString { .. } => ByteCode::PushConstString,
+            Instruction::PushConstInteger { .. } => ByteCode::PushConstInteger,
+            Instruction::PushConstFloat { .. } => ByteCode::PushConstFloat,
+            Instruction::PushConstLong { .. } => ByteCode::PushConstLong,
+            Instruction::PushConstDouble { .. } => ByteCode::PushConstDouble,
+            Instruction::PushConstBoolean { .. } => ByteCode::PushConstBoolean,
+            Instruction::GetLocal { .. } => ByteCode::GetLocal,
+            Instruction::SetLocal { .. } => ByteCode::SetLocal,
+            Instruction::ReserveLocal { .. } => ByteCode::ReserveLocal,
+            Instruction::Allocate { .. } => ByteCode::Allocate,
+            Instruction::GetField { .. } => ByteCode::GetField,
+            Instruction::SetField { .. } => ByteCode::SetField,
+            Instruction::Pop => ByteCode::Pop,
+            Instruction::Dup => ByteCode::Dup,
+            Instruction::Add => ByteCode::Add,
+            Instruction::Sub => ByteCode::Sub,
+            Instruction::Mul => ByteCode::Mul,
+            Instruction::Div => ByteCode::Div,
+            Instruction::Inc => ByteCode::Inc,
+            Instruction::Dec => ByteCode::Dec,
+            Instruction::Rem => ByteCode::Rem,
+            Instruction::Mod => ByteCode::Mod,
+            Instruction::Neg => ByteCode::Neg,
+            Instruction::And => ByteCode::And,
+            Instruction::Or => ByteCode::Or,
+            Instruction::Xor => ByteCode::Xor,
+            Instruction::Not => ByteCode::Not,
+            Instruction::Shl => ByteCode::Shl,
+            Instruction::Shr => ByteCode::Shr,
+            Instruction::BoolNot => ByteCode::BoolNot,
+            Instruction::ListNew => ByteCode::ListNew,
+            Instruction::ListPush => ByteCode::ListPush,
+            Instruction::ListPop => ByteCode::ListPop,
+            Instruction::ListLen => ByteCode::ListLen,
+            Instruction::ListGet => ByteCode::ListGet,
+            Instruction::ListSet => ByteCode::ListSet,
+            Instruction::MapNew => ByteCode::MapNew,
+            Instruction::MapInsert => ByteCode::MapInsert,
+            Instruction::MapGet => ByteCode::MapGet,
+            Instruction::MapRemove => ByteCode::MapRemove,
+            Instruction::MapContains => ByteCode::MapContains,
+            Instruction::MapLen => ByteCode::MapLen,
+            Instruction::MapKeys => ByteCode::MapKeys,
+            Instruction::StringLength => ByteCode::StringLength,
+            Instruction::StringSubstring => ByteCode::StringSubstring,
+            Instruction::StringIndexOf => ByteCode::StringIndexOf,
+            Instruction::StringSplit => ByteCode::StringSplit,
+            Instruction::StringTrim => ByteCode::StringTrim,
+            Instruction::StringUpper => ByteCode::StringUpper,
+            Instruction::StringLower => ByteCode::StringLower,
+            Instruction::StringCharCode => ByteCode::StringCharCode,
+            Instruction::StringFromCharCode => ByteCode::StringFromCharCode,
+            Instruction::StringRepeat => ByteCode::StringRepeat,
+            Instruction::ToString => ByteCode::ToString,
+            Instruction::ToInteger => ByteCode::ToInteger,
+            Instruction::ToFloat => ByteCode::ToFloat,
+            Instruction::TypeOf => ByteCode::TypeOf,
+            Instruction::IsNull => ByteCode::IsNull,
+            Instruction::FunctionRef { .. } => ByteCode::FunctionRef,
+            Instruction::GetUpvalue { .. } => ByteCode::GetUpvalue,
+            Instruction::CallIndirect { .. } => ByteCode::CallIndirect,
+            Instruction::Global { .. } => ByteCode::Global,
+            Instruction::GetGlobal { .. } => ByteCode::GetGlobal,
+            Instruction::SetGlobal { .. } => ByteCode::SetGlobal,
+            Instruction::GetProperty { .. } => ByteCode::GetProperty,
+            Instruction::SetProperty { .. } => ByteCode::SetProperty,
+            Instruction::CallMethod { .. } => ByteCode::CallMethod,
+            Instruction::ConvertInteger => ByteCode::ConvertInteger,
+            Instruction::ConvertLong => ByteCode::ConvertLong,
+            Instruction::ConvertFloat => ByteCode::ConvertFloat,
+            Instruction::ConvertDouble => ByteCode::ConvertDouble,
+            Instruction::Eq => ByteCode::Eq,
+            Instruction::Ne => ByteCode::Ne,
+            Instruction::Lt => ByteCode::Lt,
+            Instruction::Le => ByteCode::Le,
+            Instruction::Gt => ByteCode::Gt,
+            Instruction::Ge => ByteCode::Ge,
+            Instruction::Module { .. } => ByteCode::Module,
+            Instruction::Struct { .. } => ByteCode::Struct,
+            Instruction::LoadModule { .. } => ByteCode::LoadModule,
+            Instruction::GetFunction { .. } => ByteCode::GetFunction,
+            Instruction::Return => ByteCode::Return,
+            Instruction::Then { .. } => ByteCode::Then,
+            Instruction::Loop { .. } => ByteCode::Loop,
+            Instruction::Break => ByteCode::Break,
+            Instruction::Continue => ByteCode::Continue,
+            Instruction::Jump { .. } => ByteCode::Jump,
+            Instruction::JumpIfFalse { .. } => ByteCode::JumpIfFalse,
+            Instruction::Try { .. } => ByteCode::Try,
+            Instruction::TryStart { .. } => ByteCode::TryStart,
+            Instruction::TryEnd => ByteCode::TryEnd,
+            Instruction::Throw => ByteCode::Throw,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         let mut writer = ByteWriter::new(&mut bytes);
@@ -578,6 +1221,19 @@ impl<'a> Instruction {
             }
             Instruction::Dump => writer.write_byte(ByteCode::Dump as u8),
             Instruction::Hi => writer.write_byte(ByteCode::Hi as u8),
+            Instruction::Line { line } => {
+                writer.write_byte(ByteCode::Line as u8);
+                writer.write_u32(*line);
+            }
+            Instruction::Params { count } => {
+                writer.write_byte(ByteCode::Params as u8);
+                writer.write_u32(*count);
+            }
+            Instruction::Invoke { index, param_count } => {
+                writer.write_byte(ByteCode::Invoke as u8);
+                writer.write_u32(*index);
+                writer.write_u32(*param_count);
+            }
             Instruction::Fn { name, code } => {
                 writer.write_byte(ByteCode::Func as u8);
 
@@ -609,6 +1265,14 @@ impl<'a> Instruction {
                 writer.write_byte(ByteCode::PushConstFloat as u8);
                 writer.write_f32(*value);
             }
+            Instruction::PushConstLong { value } => {
+                writer.write_byte(ByteCode::PushConstLong as u8);
+                writer.write_i64(*value);
+            }
+            Instruction::PushConstDouble { value } => {
+                writer.write_byte(ByteCode::PushConstDouble as u8);
+                writer.write_f64(*value);
+            }
             Instruction::PushConstBoolean { value } => {
                 writer.write_byte(ByteCode::PushConstBoolean as u8);
                 writer.write_bool(*value);
@@ -645,6 +1309,98 @@ impl<'a> Instruction {
             Instruction::Div => writer.write_byte(ByteCode::Div as u8),
             Instruction::Inc => writer.write_byte(ByteCode::Inc as u8),
             Instruction::Dec => writer.write_byte(ByteCode::Dec as u8),
+            Instruction::Rem => writer.write_byte(ByteCode::Rem as u8),
+            Instruction::Mod => writer.write_byte(ByteCode::Mod as u8),
+            Instruction::Neg => writer.write_byte(ByteCode::Neg as u8),
+            Instruction::And => writer.write_byte(ByteCode::And as u8),
+            Instruction::Or => writer.write_byte(ByteCode::Or as u8),
+            Instruction::Xor => writer.write_byte(ByteCode::Xor as u8),
+            Instruction::Not => writer.write_byte(ByteCode::Not as u8),
+            Instruction::Shl => writer.write_byte(ByteCode::Shl as u8),
+            Instruction::Shr => writer.write_byte(ByteCode::Shr as u8),
+            Instruction::BoolNot => writer.write_byte(ByteCode::BoolNot as u8),
+            Instruction::ListNew => writer.write_byte(ByteCode::ListNew as u8),
+            Instruction::ListPush => writer.write_byte(ByteCode::ListPush as u8),
+            Instruction::ListPop => writer.write_byte(ByteCode::ListPop as u8),
+            Instruction::ListLen => writer.write_byte(ByteCode::ListLen as u8),
+            Instruction::ListGet => writer.write_byte(ByteCode::ListGet as u8),
+            Instruction::ListSet => writer.write_byte(ByteCode::ListSet as u8),
+            Instruction::MapNew => writer.write_byte(ByteCode::MapNew as u8),
+            Instruction::MapInsert => writer.write_byte(ByteCode::MapInsert as u8),
+            Instruction::MapGet => writer.write_byte(ByteCode::MapGet as u8),
+            Instruction::MapRemove => writer.write_byte(ByteCode::MapRemove as u8),
+            Instruction::MapContains => writer.write_byte(ByteCode::MapContains as u8),
+            Instruction::MapLen => writer.write_byte(ByteCode::MapLen as u8),
+            Instruction::MapKeys => writer.write_byte(ByteCode::MapKeys as u8),
+            Instruction::StringLength => writer.write_byte(ByteCode::StringLength as u8),
+            Instruction::StringSubstring => writer.write_byte(ByteCode::StringSubstring as u8),
+            Instruction::StringIndexOf => writer.write_byte(ByteCode::StringIndexOf as u8),
+            Instruction::StringSplit => writer.write_byte(ByteCode::StringSplit as u8),
+            Instruction::StringTrim => writer.write_byte(ByteCode::StringTrim as u8),
+            Instruction::StringUpper => writer.write_byte(ByteCode::StringUpper as u8),
+            Instruction::StringLower => writer.write_byte(ByteCode::StringLower as u8),
+            Instruction::StringCharCode => writer.write_byte(ByteCode::StringCharCode as u8),
+            Instruction::StringFromCharCode => {
+                writer.write_byte(ByteCode::StringFromCharCode as u8)
+            }
+            Instruction::StringRepeat => writer.write_byte(ByteCode::StringRepeat as u8),
+            Instruction::ToString => writer.write_byte(ByteCode::ToString as u8),
+            Instruction::ToInteger => writer.write_byte(ByteCode::ToInteger as u8),
+            Instruction::ToFloat => writer.write_byte(ByteCode::ToFloat as u8),
+            Instruction::TypeOf => writer.write_byte(ByteCode::TypeOf as u8),
+            Instruction::IsNull => writer.write_byte(ByteCode::IsNull as u8),
+            Instruction::FunctionRef {
+                module,
+                function,
+                captures,
+            } => {
+                writer.write_byte(ByteCode::FunctionRef as u8);
+                writer.write_string(module);
+                writer.write_string(function);
+                writer.write_u32(*captures);
+            }
+            Instruction::GetUpvalue { index } => {
+                writer.write_byte(ByteCode::GetUpvalue as u8);
+                writer.write_u32(*index);
+            }
+            Instruction::CallIndirect { param_count } => {
+                writer.write_byte(ByteCode::CallIndirect as u8);
+                writer.write_u32(*param_count);
+            }
+            Instruction::Global { name, init } => {
+                writer.write_byte(ByteCode::Global as u8);
+
+                let init_bytes = Instruction::code_to_bytes(init);
+
+                writer.write_u32(init_bytes.len() as u32);
+                writer.write_string(name);
+                writer.write_bytes(&init_bytes);
+            }
+            Instruction::GetGlobal { name } => {
+                writer.write_byte(ByteCode::GetGlobal as u8);
+                writer.write_string(name);
+            }
+            Instruction::SetGlobal { name } => {
+                writer.write_byte(ByteCode::SetGlobal as u8);
+                writer.write_string(name);
+            }
+            Instruction::GetProperty { name } => {
+                writer.write_byte(ByteCode::GetProperty as u8);
+                writer.write_string(name);
+            }
+            Instruction::SetProperty { name } => {
+                writer.write_byte(ByteCode::SetProperty as u8);
+                writer.write_string(name);
+            }
+            Instruction::CallMethod { name, param_count } => {
+                writer.write_byte(ByteCode::CallMethod as u8);
+                writer.write_string(name);
+                writer.write_u32(*param_count);
+            }
+            Instruction::ConvertInteger => writer.write_byte(ByteCode::ConvertInteger as u8),
+            Instruction::ConvertLong => writer.write_byte(ByteCode::ConvertLong as u8),
+            Instruction::ConvertFloat => writer.write_byte(ByteCode::ConvertFloat as u8),
+            Instruction::ConvertDouble => writer.write_byte(ByteCode::ConvertDouble as u8),
             Instruction::Eq => writer.write_byte(ByteCode::Eq as u8),
             Instruction::Ne => writer.write_byte(ByteCode::Ne as u8),
             Instruction::Lt => writer.write_byte(ByteCode::Lt as u8),
@@ -660,6 +1416,15 @@ impl<'a> Instruction {
                 writer.write_string(name);
                 writer.write_bytes(&code_bytes);
             }
+            Instruction::Struct { name, code } => {
+                writer.write_byte(ByteCode::Struct as u8);
+
+                let code_bytes = Instruction::code_to_bytes(code);
+
+                writer.write_u32(code_bytes.len() as u32);
+                writer.write_string(name);
+                writer.write_bytes(&code_bytes);
+            }
             Instruction::LoadModule { name, code } => {
                 writer.write_byte(ByteCode::LoadModule as u8);
 
@@ -709,6 +1474,38 @@ impl<'a> Instruction {
             }
             Instruction::Break => writer.write_byte(ByteCode::Break as u8),
             Instruction::Continue => writer.write_byte(ByteCode::Continue as u8),
+            Instruction::Jump { target } => {
+                writer.write_byte(ByteCode::Jump as u8);
+                writer.write_u32(*target);
+            }
+            Instruction::JumpIfFalse { target } => {
+                writer.write_byte(ByteCode::JumpIfFalse as u8);
+                writer.write_u32(*target);
+            }
+            Instruction::Try {
+                try_block,
+                catch_block,
+            } => {
+                writer.write_byte(ByteCode::Try as u8);
+
+                let block_bytes = Instruction::code_to_bytes(try_block);
+
+                writer.write_u32(block_bytes.len() as u32);
+                writer.write_bytes(&block_bytes);
+
+                writer.write_byte(ByteCode::Catch as u8);
+
+                let block_bytes = Instruction::code_to_bytes(catch_block);
+
+                writer.write_u32(block_bytes.len() as u32);
+                writer.write_bytes(&block_bytes);
+            }
+            Instruction::TryStart { handler } => {
+                writer.write_byte(ByteCode::TryStart as u8);
+                writer.write_u32(*handler);
+            }
+            Instruction::TryEnd => writer.write_byte(ByteCode::TryEnd as u8),
+            Instruction::Throw => writer.write_byte(ByteCode::Throw as u8),
         }
 
         bytes
@@ -759,6 +1556,14 @@ impl<'a> Instruction {
                     }
                     "dump" => Ok(Instruction::Dump),
                     "hi" => Ok(Instruction::Hi),
+                    "line" => {
+                        let line = match it.next() {
+                            Some(SExpr::Atom(value)) => value.parse::<u32>().unwrap(),
+                            _ => return Err("Expected line number".to_string()),
+                        };
+
+                        Ok(Instruction::Line { line })
+                    }
                     "fn" => {
                         let name = match it.next() {
                             Some(SExpr::Atom(value)) => value,
@@ -777,6 +1582,27 @@ impl<'a> Instruction {
                             code,
                         })
                     }
+                    "fn.params" => {
+                        let count = match it.next() {
+                            Some(SExpr::Atom(value)) => value.parse::<u32>().unwrap(),
+                            _ => return Err("Expected parameter count".to_string()),
+                        };
+
+                        Ok(Instruction::Params { count })
+                    }
+                    "invoke" => {
+                        let index = match it.next() {
+                            Some(SExpr::Atom(value)) => value.parse::<u32>().unwrap(),
+                            _ => return Err("Expected function index".to_string()),
+                        };
+
+                        let param_count = match it.next() {
+                            Some(SExpr::Atom(value)) => value.parse::<u32>().unwrap(),
+                            _ => return Err("Expected parameter count".to_string()),
+                        };
+
+                        Ok(Instruction::Invoke { index, param_count })
+                    }
                     "call" => {
                         let module = match it.next() {
                             Some(SExpr::Atom(value)) => value,
@@ -825,6 +1651,26 @@ impl<'a> Instruction {
 
                         Ok(Instruction::PushConstFloat { value })
                     }
+                    "i64.const" => {
+                        let value = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<i64>()
+                                .map_err(|_| format!("Invalid long value '{}'", value))?,
+                            _ => return Err("Expected long value".to_string()),
+                        };
+
+                        Ok(Instruction::PushConstLong { value })
+                    }
+                    "f64.const" => {
+                        let value = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<f64>()
+                                .map_err(|_| format!("Invalid double value '{}'", value))?,
+                            _ => return Err("Expected double value".to_string()),
+                        };
+
+                        Ok(Instruction::PushConstDouble { value })
+                    }
                     "bool.const" => {
                         let value = match it.next() {
                             Some(SExpr::Atom(value)) => value.parse::<bool>().unwrap(),
@@ -889,6 +1735,153 @@ impl<'a> Instruction {
                     "op.div" => Ok(Instruction::Div),
                     "op.inc" => Ok(Instruction::Inc),
                     "op.dec" => Ok(Instruction::Dec),
+                    "op.rem" => Ok(Instruction::Rem),
+                    "op.mod" => Ok(Instruction::Mod),
+                    "op.neg" => Ok(Instruction::Neg),
+                    "op.and" => Ok(Instruction::And),
+                    "op.or" => Ok(Instruction::Or),
+                    "op.xor" => Ok(Instruction::Xor),
+                    "op.not" => Ok(Instruction::Not),
+                    "op.shl" => Ok(Instruction::Shl),
+                    "op.shr" => Ok(Instruction::Shr),
+                    "bool.not" => Ok(Instruction::BoolNot),
+                    "list.new" => Ok(Instruction::ListNew),
+                    "list.push" => Ok(Instruction::ListPush),
+                    "list.pop" => Ok(Instruction::ListPop),
+                    "list.len" => Ok(Instruction::ListLen),
+                    "list.get" => Ok(Instruction::ListGet),
+                    "list.set" => Ok(Instruction::ListSet),
+                    "map.new" => Ok(Instruction::MapNew),
+                    "map.insert" => Ok(Instruction::MapInsert),
+                    "map.get" => Ok(Instruction::MapGet),
+                    "map.remove" => Ok(Instruction::MapRemove),
+                    "map.contains" => Ok(Instruction::MapContains),
+                    "map.len" => Ok(Instruction::MapLen),
+                    "map.keys" => Ok(Instruction::MapKeys),
+                    "str.len" => Ok(Instruction::StringLength),
+                    "str.sub" => Ok(Instruction::StringSubstring),
+                    "str.index_of" => Ok(Instruction::StringIndexOf),
+                    "str.split" => Ok(Instruction::StringSplit),
+                    "str.trim" => Ok(Instruction::StringTrim),
+                    "str.upper" => Ok(Instruction::StringUpper),
+                    "str.lower" => Ok(Instruction::StringLower),
+                    "str.code" => Ok(Instruction::StringCharCode),
+                    "str.from_code" => Ok(Instruction::StringFromCharCode),
+                    "str.repeat" => Ok(Instruction::StringRepeat),
+                    "to.string" => Ok(Instruction::ToString),
+                    "to.int" => Ok(Instruction::ToInteger),
+                    "to.float" => Ok(Instruction::ToFloat),
+                    "type.of" => Ok(Instruction::TypeOf),
+                    "is.null" => Ok(Instruction::IsNull),
+                    "fn.ref" => {
+                        let module = match it.next() {
+                            Some(SExpr::Atom(value)) => value,
+                            _ => return Err("Expected module name".to_string()),
+                        };
+
+                        let function = match it.next() {
+                            Some(SExpr::Atom(value)) => value,
+                            _ => return Err("Expected function name".to_string()),
+                        };
+
+                        // (fn.ref main add) references the function without captures
+                        let captures = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<u32>()
+                                .map_err(|_| format!("Invalid capture count '{}'", value))?,
+                            None => 0,
+                            _ => return Err("Expected capture count".to_string()),
+                        };
+
+                        Ok(Instruction::FunctionRef {
+                            module: module.to_string(),
+                            function: function.to_string(),
+                            captures,
+                        })
+                    }
+                    "upvalue.get" => {
+                        let index = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<u32>()
+                                .map_err(|_| format!("Invalid upvalue index '{}'", value))?,
+                            _ => return Err("Expected upvalue index".to_string()),
+                        };
+
+                        Ok(Instruction::GetUpvalue { index })
+                    }
+                    "call.indirect" => {
+                        let param_count = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<u32>()
+                                .map_err(|_| format!("Invalid parameter count '{}'", value))?,
+                            _ => return Err("Expected parameter count".to_string()),
+                        };
+
+                        Ok(Instruction::CallIndirect { param_count })
+                    }
+                    "global" => {
+                        let name = match it.next() {
+                            Some(SExpr::Atom(value)) => value,
+                            _ => return Err("Expected global name".to_string()),
+                        };
+
+                        let mut init = Vec::new();
+
+                        for value in it {
+                            init.push(Instruction::from_sexpr(value)?);
+                        }
+
+                        Ok(Instruction::Global {
+                            name: name.to_string(),
+                            init,
+                        })
+                    }
+                    "global.get" => match it.next() {
+                        Some(SExpr::Atom(name)) => Ok(Instruction::GetGlobal {
+                            name: name.to_string(),
+                        }),
+                        _ => Err("Expected global name".to_string()),
+                    },
+                    "global.set" => match it.next() {
+                        Some(SExpr::Atom(name)) => Ok(Instruction::SetGlobal {
+                            name: name.to_string(),
+                        }),
+                        _ => Err("Expected global name".to_string()),
+                    },
+                    "prop.get" => match it.next() {
+                        Some(SExpr::Atom(name)) => Ok(Instruction::GetProperty {
+                            name: name.to_string(),
+                        }),
+                        _ => Err("Expected property name".to_string()),
+                    },
+                    "prop.set" => match it.next() {
+                        Some(SExpr::Atom(name)) => Ok(Instruction::SetProperty {
+                            name: name.to_string(),
+                        }),
+                        _ => Err("Expected property name".to_string()),
+                    },
+                    "call.method" => {
+                        let name = match it.next() {
+                            Some(SExpr::Atom(value)) => value,
+                            _ => return Err("Expected method name".to_string()),
+                        };
+
+                        let param_count = match it.next() {
+                            Some(SExpr::Atom(value)) => value
+                                .parse::<u32>()
+                                .map_err(|_| format!("Invalid parameter count '{}'", value))?,
+                            _ => return Err("Expected parameter count".to_string()),
+                        };
+
+                        Ok(Instruction::CallMethod {
+                            name: name.to_string(),
+                            param_count,
+                        })
+                    }
+                    "conv.i32" => Ok(Instruction::ConvertInteger),
+                    "conv.i64" => Ok(Instruction::ConvertLong),
+                    "conv.f32" => Ok(Instruction::ConvertFloat),
+                    "conv.f64" => Ok(Instruction::ConvertDouble),
                     "cmp.eq" => Ok(Instruction::Eq),
                     "cmp.ne" => Ok(Instruction::Ne),
                     "cmp.lt" => Ok(Instruction::Lt),
@@ -913,6 +1906,23 @@ impl<'a> Instruction {
                             code: module_code,
                         })
                     }
+                    "struct" => {
+                        let name = match it.next() {
+                            Some(SExpr::Atom(value)) => value,
+                            _ => return Err("Expected struct name".to_string()),
+                        };
+
+                        let mut struct_code = Vec::new();
+
+                        for value in it {
+                            struct_code.push(Instruction::from_sexpr(value)?);
+                        }
+
+                        Ok(Instruction::Struct {
+                            name: name.to_string(),
+                            code: struct_code,
+                        })
+                    }
                     "mod.load" => {
                         let name = match it.next() {
                             Some(SExpr::Atom(value)) => value,
@@ -1011,6 +2021,64 @@ impl<'a> Instruction {
                     }
                     "break" => Ok(Instruction::Break),
                     "continue" => Ok(Instruction::Continue),
+                    "jump" | "jump.false" => {
+                        let target = match it.next() {
+                            Some(SExpr::Atom(value)) => value.parse::<u32>().unwrap(),
+                            _ => return Err("Expected jump target".to_string()),
+                        };
+
+                        if name == "jump" {
+                            Ok(Instruction::Jump { target })
+                        } else {
+                            Ok(Instruction::JumpIfFalse { target })
+                        }
+                    }
+                    "try" => {
+                        let mut try_block = Vec::new();
+                        let mut catch_block = Vec::new();
+
+                        let mut has_catch = false;
+
+                        for value in it.by_ref() {
+                            match value {
+                                SExpr::Atom(value) if value == "catch" => {
+                                    has_catch = true;
+                                    break;
+                                }
+                                SExpr::Atom(_) => return Err("Unexpected atom".to_string()),
+                                SExpr::List(_) => try_block.push(Instruction::from_sexpr(value)?),
+                            }
+                        }
+
+                        if !has_catch {
+                            return Err("Expected catch block".to_string());
+                        }
+
+                        for value in it.by_ref() {
+                            match value {
+                                SExpr::List(_) => catch_block.push(Instruction::from_sexpr(value)?),
+                                _ => return Err("Unexpected atom".to_string()),
+                            }
+                        }
+
+                        Ok(Instruction::Try {
+                            try_block,
+                            catch_block,
+                        })
+                    }
+                    "try.start" => {
+                        let Some(SExpr::Atom(value)) = it.next() else {
+                            return Err("Expected handler target".to_string());
+                        };
+
+                        let handler = value
+                            .parse::<u32>()
+                            .map_err(|_| format!("Invalid handler target '{}'", value))?;
+
+                        Ok(Instruction::TryStart { handler })
+                    }
+                    "try.end" => Ok(Instruction::TryEnd),
+                    "throw" => Ok(Instruction::Throw),
                     _ => Err(format!("Unknown instruction: {}", name)),
                 }
             }
@@ -1,29 +1,60 @@
+mod arithmetic;
 pub mod asm;
 mod builder;
 mod byte_reader;
 mod byte_writer;
 mod bytecode;
+mod conversion;
 pub mod dymodule;
+mod frame;
 mod function;
+mod heap;
 mod instruction;
+mod linker;
+mod lower;
 mod module;
+mod native;
 pub(crate) mod parser;
+mod plugin;
+mod runtime_error;
 pub(crate) mod sexpr;
+mod strings;
+mod typed;
 mod value;
 mod virtual_machine;
 
-use std::collections::HashMap;
+use std::path::PathBuf;
 
+pub use arithmetic::Overflow;
 pub use builder::*;
 pub use bytecode::*;
 pub use dymodule::*;
+pub use frame::*;
 pub use function::*;
+pub use heap::*;
 pub use instruction::*;
+pub use lower::*;
 pub use module::*;
+pub use plugin::*;
+pub use runtime_error::*;
+pub use typed::*;
 pub use value::*;
 pub use virtual_machine::*;
 
+pub use ms_derive::{FromValue, ToValue};
+
+// Lets the code generated by the derives name the crate from inside it too
+extern crate self as ms_runtime;
+
+// Load the modules of `code`, native modules are searched in the default `search_paths`
 pub fn load_modules(code: &Code) -> Result<(Vec<Module>, Vec<DyModule>), String> {
+    load_modules_from(code, &search_paths(&[], None))
+}
+
+pub fn load_modules_from(
+    code: &Code,
+    search_paths: &[PathBuf],
+) -> Result<(Vec<Module>, Vec<DyModule>), String> {
     // validate version
 
     let version = code.get(0).ok_or("Missing version")?;
@@ -59,36 +90,7 @@ pub fn load_modules(code: &Code) -> Result<(Vec<Module>, Vec<DyModule>), String>
                 modules.push(Module::try_from(instruction.clone()).map_err(|e| e.to_string())?);
             }
             Instruction::LoadModule { name, code } => {
-                dy_modules.push(DyModule {
-                    name: name.clone(),
-                    lib: unsafe { libloading::Library::new(name).map_err(|e| e.to_string())? },
-                    fns: HashMap::new(),
-                });
-
-                let dymodule = dy_modules.last_mut().unwrap();
-
-                for instruction in code.iter() {
-                    match instruction {
-                        Instruction::GetFunction { name, alias } => {
-                            let symbol = name.clone();
-                            let func: libloading::Symbol<'_, fn(Vec<Value>) -> Option<Value>> = unsafe {
-                                dymodule
-                                    .lib
-                                    .get(symbol.as_bytes())
-                                    .map_err(|e| e.to_string())?
-                            };
-
-                            if let Some(alias) = alias {
-                                dymodule.fns.insert(alias.clone(), Box::new(*func));
-                            } else {
-                                dymodule.fns.insert(name.clone(), Box::new(*func));
-                            }
-                        }
-                        _ => {
-                            return Err("Invalid instruction type, expected (fn.get)".to_string());
-                        }
-                    }
-                }
+                dy_modules.push(DyModule::load(name, code, search_paths)?);
             }
             _ => {
                 return Err("Invalid instruction type, expected (mod) or (mod.load)".to_string());
@@ -0,0 +1,208 @@
+use std::rc::Rc;
+
+use crate::{virtual_machine::Callee, Function, Instruction, VirtualMachine};
+
+impl VirtualMachine {
+    // Resolve the (call) instructions of every loaded function to (invoke) instructions
+    // indexing the VM's function table, so calls skip the module and function lookups.
+    // Unresolved calls and calls with the wrong number of arguments are reported, one per line
+    pub fn link(&mut self) -> Result<(), String> {
+        let links = &mut self.links;
+        let functions = &mut self.functions;
+
+        // Indices given by an earlier link are kept so code it linked stays valid
+        let mut define = |module: &str, name: &str, callee: Callee| {
+            let key = (module.to_string(), name.to_string());
+
+            match links.get(&key) {
+                Some(index) => functions[*index as usize] = callee,
+                None => {
+                    links.insert(key, functions.len() as u32);
+                    functions.push(callee);
+                }
+            }
+        };
+
+        for module in self.modules.values_mut() {
+            for function in module.all_functions_mut() {
+                define(
+                    &function.module,
+                    &function.name,
+                    Callee::Script(function.clone()),
+                );
+            }
+        }
+
+        for dymodule in self.dymodules.values() {
+            for (name, function) in dymodule.fns.iter() {
+                define(
+                    &dymodule.name,
+                    name,
+                    Callee::Native(dymodule.lib.clone(), *function),
+                );
+            }
+        }
+
+        for (module, natives) in self.natives.iter() {
+            for (name, function) in natives.iter() {
+                define(module, name, Callee::Host(function.clone()));
+            }
+        }
+
+        let mut errors = vec![];
+
+        for module in self.modules.values_mut() {
+            for function in module.all_functions_mut() {
+                let code = function
+                    .code
+                    .iter()
+                    .map(|instruction| {
+                        let Instruction::Call {
+                            module,
+                            function: target,
+                            param_count,
+                        } = instruction
+                        else {
+                            // Function values are resolved when they are created, only
+                            // check that the function exists
+                            if let Instruction::FunctionRef {
+                                module,
+                                function: target,
+                                ..
+                            } = instruction
+                            {
+                                if !self.links.contains_key(&(module.clone(), target.clone())) {
+                                    errors.push(format!(
+                                        "{}.{}: Unresolved reference to {}.{}",
+                                        function.module, function.name, module, target
+                                    ));
+                                }
+                            }
+
+                            return instruction.clone();
+                        };
+
+                        let key = (module.clone(), target.clone());
+
+                        let Some(index) = self.links.get(&key) else {
+                            errors.push(format!(
+                                "{}.{}: Unresolved call to {}.{}",
+                                function.module, function.name, module, target
+                            ));
+
+                            return instruction.clone();
+                        };
+
+                        if let Callee::Script(target) = &self.functions[*index as usize] {
+                            if target.params.is_some_and(|params| params != *param_count) {
+                                errors.push(format!(
+                                    "{}.{}: {}.{} expects {} argument(s), called with {}",
+                                    function.module,
+                                    function.name,
+                                    target.module,
+                                    target.name,
+                                    target.params.unwrap_or_default(),
+                                    param_count
+                                ));
+                            }
+                        }
+
+                        Instruction::Invoke {
+                            index: *index,
+                            param_count: *param_count,
+                        }
+                    })
+                    .collect();
+
+                *function = Rc::new(Function {
+                    module: function.module.clone(),
+                    name: function.name.clone(),
+                    params: function.params,
+                    code,
+                });
+
+                // A host or plugin function defined with the same name keeps its entry
+                let index = self.links[&(function.module.clone(), function.name.clone())];
+                let callee = &mut self.functions[index as usize];
+
+                if matches!(callee, Callee::Script(_)) {
+                    *callee = Callee::Script(function.clone());
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort();
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{asm::assemble, load_modules, Instruction, Value, VirtualMachine};
+
+    fn vm(source: &str) -> VirtualMachine {
+        let (modules, _) = load_modules(&assemble(source).unwrap()).unwrap();
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        vm
+    }
+
+    #[test]
+    fn link_resolves_calls() {
+        let mut vm = vm("(mod main
+            (struct Foo (fn get (fn.params 1) (local.get 0)))
+            (fn add (fn.params 2) (local.get 0) (local.get 1) (op.add))
+            (fn main (i32.const 1) (i32.const 2) (call main add 2) (call main Foo.get 1))
+        )");
+
+        vm.link().unwrap();
+
+        let code = &vm.get_function("main", "main").unwrap().code;
+        assert!(matches!(
+            code[2],
+            Instruction::Invoke { param_count: 2, .. }
+        ));
+        assert!(matches!(
+            code[3],
+            Instruction::Invoke { param_count: 1, .. }
+        ));
+
+        assert!(matches!(
+            vm.call("main", "main", vec![]),
+            Ok(Some(Value::Integer(3)))
+        ));
+
+        // Linking again keeps the indices of the code linked before
+        vm.link().unwrap();
+
+        assert!(matches!(
+            vm.call("main", "main", vec![]),
+            Ok(Some(Value::Integer(3)))
+        ));
+    }
+
+    #[test]
+    fn link_errors() {
+        let mut vm = vm("(mod main
+            (fn add (fn.params 2) (local.get 0) (local.get 1) (op.add))
+            (fn main (i32.const 1) (call main add 1) (call other f 0) (call main nope 0))
+            (fn get (fn.ref main missing))
+        )");
+
+        assert_eq!(
+            vm.link().unwrap_err(),
+            "main.get: Unresolved reference to main.missing\n\
+             main.main: Unresolved call to main.nope\n\
+             main.main: Unresolved call to other.f\n\
+             main.main: main.add expects 2 argument(s), called with 1"
+        );
+    }
+}
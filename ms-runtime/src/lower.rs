@@ -0,0 +1,204 @@
+use crate::{Code, Instruction};
+
+struct Loop {
+    start: u32,
+    // Jumps emitted for (break) that are patched to the end of the loop
+    breaks: Vec<usize>,
+    // (try) blocks open inside the loop, leaving the loop removes their handlers
+    tries: usize,
+}
+
+// Lower the nested (then), (loop) and (try) blocks of a function body into the flat instruction
+// stream the VM executes, with (jump) and (jump.false) targets indexing into the result
+pub fn lower(code: &Code) -> Code {
+    let mut lowered = vec![];
+
+    lower_block(code, &mut vec![], &mut lowered);
+
+    lowered
+}
+
+fn lower_block(code: &Code, loops: &mut Vec<Loop>, lowered: &mut Code) {
+    for instruction in code.iter() {
+        match instruction {
+            Instruction::Then {
+                then_block,
+                else_block,
+            } => {
+                let jump_else = lowered.len();
+                lowered.push(Instruction::JumpIfFalse { target: 0 });
+
+                lower_block(then_block, loops, lowered);
+
+                if else_block.is_empty() {
+                    patch(lowered, jump_else);
+                } else {
+                    let jump_end = lowered.len();
+                    lowered.push(Instruction::Jump { target: 0 });
+
+                    patch(lowered, jump_else);
+                    lower_block(else_block, loops, lowered);
+                    patch(lowered, jump_end);
+                }
+            }
+            Instruction::Loop { block } => {
+                let start = lowered.len() as u32;
+
+                loops.push(Loop {
+                    start,
+                    breaks: vec![],
+                    tries: 0,
+                });
+
+                lower_block(block, loops, lowered);
+                lowered.push(Instruction::Jump { target: start });
+
+                let lowered_loop = loops.pop().expect("Loop was pushed above");
+
+                for jump in lowered_loop.breaks {
+                    patch(lowered, jump);
+                }
+            }
+            Instruction::Try {
+                try_block,
+                catch_block,
+            } => {
+                let start = lowered.len();
+                lowered.push(Instruction::TryStart { handler: 0 });
+
+                enter_try(loops, 1);
+                lower_block(try_block, loops, lowered);
+                enter_try(loops, -1);
+
+                lowered.push(Instruction::TryEnd);
+
+                let jump_end = lowered.len();
+                lowered.push(Instruction::Jump { target: 0 });
+
+                patch(lowered, start);
+                lower_block(catch_block, loops, lowered);
+                patch(lowered, jump_end);
+            }
+            // Outside of a loop (break) and (continue) leave the function, as the block
+            // interpreter used to do
+            Instruction::Break => match loops.last_mut() {
+                Some(current) => {
+                    end_tries(current, lowered);
+                    current.breaks.push(lowered.len());
+                    lowered.push(Instruction::Jump { target: 0 });
+                }
+                None => lowered.push(Instruction::Return),
+            },
+            Instruction::Continue => match loops.last() {
+                Some(current) => {
+                    end_tries(current, lowered);
+                    lowered.push(Instruction::Jump {
+                        target: current.start,
+                    });
+                }
+                None => lowered.push(Instruction::Return),
+            },
+            _ => lowered.push(instruction.clone()),
+        }
+    }
+}
+
+fn enter_try(loops: &mut [Loop], delta: isize) {
+    if let Some(current) = loops.last_mut() {
+        current.tries = current
+            .tries
+            .checked_add_signed(delta)
+            .expect("Tries are balanced");
+    }
+}
+
+// Remove the handlers of the (try) blocks a (break) or (continue) jumps out of
+fn end_tries(current: &Loop, lowered: &mut Code) {
+    for _ in 0..current.tries {
+        lowered.push(Instruction::TryEnd);
+    }
+}
+
+// Point the jump at `index` to the next instruction to be emitted
+fn patch(lowered: &mut Code, index: usize) {
+    let next = lowered.len() as u32;
+
+    match &mut lowered[index] {
+        Instruction::Jump { target } | Instruction::JumpIfFalse { target } => *target = next,
+        Instruction::TryStart { handler } => *handler = next,
+        _ => unreachable!("Only jumps are patched"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn lower_fn(source: &str) -> Code {
+        let code = assemble(&format!("(mod main (fn main {}))", source)).unwrap();
+
+        match &code[1] {
+            Instruction::Module { code, .. } => match &code[0] {
+                Instruction::Fn { code, .. } => lower(code),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn lower_then_else() {
+        let code = lower_fn("(bool.const true) (then (i32.const 1) else (i32.const 2)) (pop)");
+
+        assert!(matches!(
+            code.as_slice(),
+            [
+                Instruction::PushConstBoolean { value: true },
+                Instruction::JumpIfFalse { target: 4 },
+                Instruction::PushConstInteger { value: 1 },
+                Instruction::Jump { target: 5 },
+                Instruction::PushConstInteger { value: 2 },
+                Instruction::Pop,
+            ]
+        ));
+    }
+
+    #[test]
+    fn lower_nested_loops() {
+        let code =
+            lower_fn("(loop (hi) (loop (break) (continue)) (bool.const true) (then (break)))");
+
+        assert!(matches!(
+            code.as_slice(),
+            [
+                Instruction::Hi,
+                Instruction::Jump { target: 4 },
+                Instruction::Jump { target: 1 },
+                Instruction::Jump { target: 1 },
+                Instruction::PushConstBoolean { value: true },
+                Instruction::JumpIfFalse { target: 7 },
+                Instruction::Jump { target: 8 },
+                Instruction::Jump { target: 0 },
+            ]
+        ));
+    }
+
+    #[test]
+    fn lower_try_in_loop() {
+        let code = lower_fn("(loop (try (break) catch (pop)))");
+
+        assert!(matches!(
+            code.as_slice(),
+            [
+                Instruction::TryStart { handler: 5 },
+                Instruction::TryEnd,
+                Instruction::Jump { target: 7 },
+                Instruction::TryEnd,
+                Instruction::Jump { target: 6 },
+                Instruction::Pop,
+                Instruction::Jump { target: 0 },
+            ]
+        ));
+    }
+}
@@ -1,11 +1,59 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::instruction::{Code, Instruction};
-use crate::Function;
+use crate::{Function, Value};
 
 pub struct Module {
     pub name: String,
-    pub functions: HashMap<String, Box<Function>>,
+    pub functions: HashMap<String, Rc<Function>>,
+    pub structs: HashMap<String, StructType>,
+    // Current values of the module's globals
+    pub globals: HashMap<String, Value>,
+}
+
+// Functions scoped to a struct, called as `Struct.function`. Instance methods
+// receive the object they are called on as their first argument (local 0).
+pub struct StructType {
+    pub name: String,
+    pub functions: HashMap<String, Rc<Function>>,
+}
+
+impl StructType {
+    // Load a (struct) declared in `module`
+    pub fn from_instruction(module: &str, value: &Instruction) -> Result<StructType, String> {
+        let Instruction::Struct { name, code } = value else {
+            return Err("Invalid instruction type, expected (struct)".to_string());
+        };
+
+        let mut struct_type = StructType {
+            name: name.clone(),
+            functions: HashMap::new(),
+        };
+
+        for instruction in code.iter() {
+            match instruction {
+                Instruction::Fn {
+                    name: function,
+                    code,
+                } => {
+                    struct_type.functions.insert(
+                        function.clone(),
+                        Rc::new(Function::new(
+                            module,
+                            &format!("{}.{}", name, function),
+                            code,
+                        )),
+                    );
+                }
+                _ => {
+                    return Err("Invalid instruction type, expected (fn)".to_string());
+                }
+            }
+        }
+
+        Ok(struct_type)
+    }
 }
 
 impl TryFrom<Instruction> for Module {
@@ -23,8 +71,24 @@ impl TryFrom<Instruction> for Module {
                         Instruction::Fn { name, code } => {
                             module.add_function(name.to_string(), code);
                         }
+                        Instruction::Struct { name: _, code: _ } => {
+                            module.add_struct(StructType::from_instruction(
+                                &module.name,
+                                instruction,
+                            )?);
+                        }
+                        Instruction::Global { name, init } => {
+                            let value = constant(init).ok_or_else(|| {
+                                format!("Global {} must be initialized with a constant", name)
+                            })?;
+
+                            module.globals.insert(name.clone(), value);
+                        }
                         _ => {
-                            return Err("Invalid instruction type, expected (fn)".to_string());
+                            return Err(
+                                "Invalid instruction type, expected (fn), (struct) or (global)"
+                                    .to_string(),
+                            );
                         }
                     }
                 }
@@ -38,29 +102,174 @@ impl TryFrom<Instruction> for Module {
     }
 }
 
+// Value of a global initializer, null when it is empty
+fn constant(init: &Code) -> Option<Value> {
+    match init.as_slice() {
+        [] => Some(Value::Null),
+        [Instruction::PushConstInteger { value }] => Some(Value::Integer(*value)),
+        [Instruction::PushConstLong { value }] => Some(Value::Long(*value)),
+        [Instruction::PushConstFloat { value }] => Some(Value::Float(*value)),
+        [Instruction::PushConstDouble { value }] => Some(Value::Double(*value)),
+        [Instruction::PushConstBoolean { value }] => Some(Value::Boolean(*value)),
+        [Instruction::PushConstString { value }] => Some(Value::String(value.clone())),
+        _ => None,
+    }
+}
+
 impl Module {
     pub fn new(name: &str) -> Module {
         Module {
             name: name.to_string(),
             functions: HashMap::new(),
+            structs: HashMap::new(),
+            globals: HashMap::new(),
         }
     }
 
+    pub fn add_struct(&mut self, struct_type: StructType) {
+        self.structs.insert(struct_type.name.clone(), struct_type);
+    }
+
     pub fn add_function(&mut self, name: String, code: &Code) {
         self.functions.insert(
             name.to_string(),
-            Box::new(Function {
-                name: name,
-                code: code.clone(),
-            }),
+            Rc::new(Function::new(&self.name, &name, code)),
         );
     }
 
     pub fn get_function(&self, name: &str) -> Option<&Function> {
-        self.functions.get(name).map(|f| &**f)
+        self.get_shared_function(name).map(|f| &**f)
+    }
+
+    // Looks up a module function, or a struct function when `name` is `Struct.function`.
+    // Functions are shared so the VM can run one without copying its code
+    pub fn get_shared_function(&self, name: &str) -> Option<&Rc<Function>> {
+        if let Some((struct_name, name)) = name.split_once('.') {
+            let struct_type = self.structs.get(struct_name)?;
+
+            return struct_type.functions.get(name);
+        }
+
+        self.functions.get(name)
     }
 
+    // Every function of the module, including struct functions
+    pub fn all_functions_mut(&mut self) -> impl Iterator<Item = &mut Rc<Function>> {
+        self.functions.values_mut().chain(
+            self.structs
+                .values_mut()
+                .flat_map(|struct_type| struct_type.functions.values_mut()),
+        )
+    }
+
+    // Only available while the function isn't shared with a running call
     pub fn get_function_mut(&mut self, name: &str) -> Option<&mut Function> {
-        self.functions.get_mut(name).map(|f| &mut **f)
+        if let Some((struct_name, name)) = name.split_once('.') {
+            let struct_type = self.structs.get_mut(struct_name)?;
+
+            return struct_type.functions.get_mut(name).and_then(Rc::get_mut);
+        }
+
+        self.functions.get_mut(name).and_then(Rc::get_mut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{asm::assemble, load_modules, ErrorKind, Instruction, Value, VirtualMachine};
+
+    #[test]
+    fn module_struct_functions() {
+        let code = assemble(
+            "(mod main
+                (struct Foo
+                    (fn new (alloc 1) (local.get 0) (field.set 0))
+                    (fn get (local.get 0) (field.get 0))
+                )
+                (fn main
+                    (i32.const 7)
+                    (call main Foo.new 1)
+                    (call main Foo.get 1)
+                )
+            )",
+        )
+        .unwrap();
+
+        // Round trip through the binary format
+        let code = Instruction::from_bytecode(&Instruction::code_to_bytes(&code)).unwrap();
+        let (modules, _) = load_modules(&code).unwrap();
+
+        let function = modules[0].get_function("Foo.new").unwrap();
+        assert_eq!(
+            (function.module.as_str(), function.name.as_str()),
+            ("main", "Foo.new")
+        );
+        assert!(modules[0].get_function("Foo.missing").is_none());
+        assert!(modules[0].get_function("Bar.new").is_none());
+
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        assert!(matches!(
+            vm.call("main", "main", vec![]),
+            Ok(Some(Value::Integer(7)))
+        ));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn module_globals() {
+        let code = assemble(
+            "(mod main
+                (global count (i32.const 0))
+                (global cache)
+                (fn next
+                    (global.get count) (i32.const 1) (op.add) (global.set count)
+                    (global.get count)
+                )
+                (fn cache (global.get cache))
+                (fn missing (global.get nope))
+            )",
+        )
+        .unwrap();
+
+        let code = Instruction::from_bytecode(&Instruction::code_to_bytes(&code)).unwrap();
+        let (modules, _) = load_modules(&code).unwrap();
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        vm.call("main", "next", vec![]).unwrap();
+        assert!(matches!(
+            vm.call("main", "next", vec![]),
+            Ok(Some(Value::Integer(2)))
+        ));
+        assert!(matches!(
+            vm.get_global("main", "count"),
+            Ok(Value::Integer(2))
+        ));
+
+        // Objects stored in globals survive collections
+        let list = vm.allocate(crate::Object::List(vec![])).unwrap();
+        vm.set_global("main", "cache", list).unwrap();
+        vm.collect_garbage();
+
+        let list = vm.call("main", "cache", vec![]).unwrap().unwrap();
+        assert_eq!(vm.type_of(&list), "list");
+
+        assert_eq!(
+            vm.call("main", "missing", vec![]).unwrap_err().kind,
+            ErrorKind::GlobalNotFound("main.nope".to_string())
+        );
+        assert_eq!(
+            vm.set_global("main", "nope", Value::Null),
+            Err(ErrorKind::GlobalNotFound("main.nope".to_string()))
+        );
+        assert!(load_modules(&assemble("(mod main (global x (list.new)))").unwrap()).is_err());
     }
 }
@@ -0,0 +1,133 @@
+use crate::{ErrorKind, Instruction, MapKey, NativeObject, Object, Value, VirtualMachine};
+
+impl VirtualMachine {
+    pub(crate) fn is_native(&self, value: &Value) -> bool {
+        let object = value.handle().and_then(|handle| self.heap.get(handle));
+
+        matches!(object, Some(Object::Native(_)))
+    }
+
+    // Native object `value` refers to
+    fn native_object(&mut self, value: &Value) -> Result<&mut dyn NativeObject, ErrorKind> {
+        let Value::Object(handle) = value else {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "native object",
+                found: value.type_name(),
+            });
+        };
+
+        match self.heap.get_mut(*handle) {
+            Some(Object::Native(native)) => Ok(native.as_mut()),
+            Some(object) => Err(ErrorKind::TypeMismatch {
+                expected: "native object",
+                found: object.type_name(),
+            }),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    // (field.get) and (field.set) on a native object, properties are keyed by the field index
+    pub(crate) fn get_native_field(
+        &mut self,
+        object: &Value,
+        index: u32,
+    ) -> Result<Value, ErrorKind> {
+        self.native_object(object)?
+            .get(&MapKey::Integer(index as i64))
+    }
+
+    pub(crate) fn set_native_field(
+        &mut self,
+        object: &Value,
+        index: u32,
+        value: Value,
+    ) -> Result<(), ErrorKind> {
+        self.native_object(object)?
+            .set(&MapKey::Integer(index as i64), value)
+    }
+
+    // Execute (prop.get), (prop.set) or (call.method)
+    pub(crate) fn native_op(&mut self, instruction: &Instruction) -> Result<(), ErrorKind> {
+        match instruction {
+            Instruction::GetProperty { name } => {
+                let object = self.pop()?;
+                let value = self
+                    .native_object(&object)?
+                    .get(&MapKey::String(name.clone()))?;
+
+                self.stack.push(value);
+            }
+            Instruction::SetProperty { name } => {
+                let value = self.pop()?;
+                let object = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+
+                self.native_object(&object)?
+                    .set(&MapKey::String(name.clone()), value)?;
+            }
+            Instruction::CallMethod { name, param_count } => {
+                let Some(start) = self.stack.len().checked_sub(*param_count as usize) else {
+                    return Err(ErrorKind::StackUnderflow);
+                };
+
+                let args = self.stack.split_off(start);
+                let object = self.pop()?;
+
+                if let Some(value) = self.native_object(&object)?.call(name, args)? {
+                    self.stack.push(value);
+                }
+            }
+            _ => return Err(ErrorKind::InvalidInstruction),
+        }
+
+        Ok(())
+    }
+
+    // Move a native object into the heap, the same rooting rules as `allocate` apply
+    pub fn new_native(&mut self, object: impl NativeObject) -> Result<Value, ErrorKind> {
+        self.allocate(Object::Native(Box::new(object)))
+    }
+
+    // The native object `value` refers to, if it is a `T`
+    pub fn native<T: NativeObject>(&self, value: &Value) -> Result<&T, ErrorKind> {
+        let mismatch = |found| ErrorKind::TypeMismatch {
+            expected: std::any::type_name::<T>(),
+            found,
+        };
+
+        let Value::Object(handle) = value else {
+            return Err(mismatch(value.type_name()));
+        };
+
+        match self.heap.get(*handle) {
+            Some(Object::Native(native)) => (**native)
+                .downcast_ref()
+                .ok_or_else(|| mismatch(native.type_name())),
+            Some(object) => Err(mismatch(object.type_name())),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    pub fn native_mut<T: NativeObject>(&mut self, value: &Value) -> Result<&mut T, ErrorKind> {
+        let mismatch = |found| ErrorKind::TypeMismatch {
+            expected: std::any::type_name::<T>(),
+            found,
+        };
+
+        let Value::Object(handle) = value else {
+            return Err(mismatch(value.type_name()));
+        };
+
+        match self.heap.get_mut(*handle) {
+            Some(Object::Native(native)) => {
+                let found = native.type_name();
+                (**native).downcast_mut().ok_or_else(|| mismatch(found))
+            }
+            Some(object) => Err(mismatch(object.type_name())),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr},
+};
+
+use crate::{ErrorKind, Value};
+
+// Version of the plugin interface. Plugins export `ms_plugin_abi_version` returning the
+// version they were built against and are only loaded if it matches. Bump it on any
+// change to the layout of `NativeApi` or the meaning of its functions
+pub const ABI_VERSION: u32 = 1;
+
+// Type of a value as seen by plugins, `MS_NULL`, `MS_INT`... in the header
+pub type ValueType = u32;
+
+const VALUE_TYPES: [&str; 8] = [
+    "MS_NULL",
+    "MS_BOOL",
+    "MS_INT",
+    "MS_LONG",
+    "MS_FLOAT",
+    "MS_DOUBLE",
+    "MS_STRING",
+    "MS_OBJECT",
+];
+
+fn value_type(value: &Value) -> ValueType {
+    match value {
+        Value::Null => 0,
+        Value::Boolean(_) => 1,
+        Value::Integer(_) => 2,
+        Value::Long(_) => 3,
+        Value::Float(_) => 4,
+        Value::Double(_) => 5,
+        Value::String(_) => 6,
+        Value::Object(_) => 7,
+    }
+}
+
+// A function registered by a plugin. It returns one of its arguments, a value created
+// with the `new_*` functions of the API, or null if it returns nothing
+pub type NativeFn = unsafe extern "C" fn(call: *mut NativeCall) -> *const Value;
+
+// A call of a native function, plugins only see it through a pointer. Values created
+// during the call are owned by it and live until the native function returns
+pub struct NativeCall {
+    args: Vec<Value>,
+    // Boxed so the pointers given to the plugin stay valid as more values are created
+    #[allow(clippy::vec_box)]
+    values: Vec<Box<Value>>,
+    error: Option<String>,
+}
+
+impl NativeCall {
+    fn value(&mut self, value: Value) -> *const Value {
+        self.values.push(Box::new(value));
+        &**self.values.last().expect("Value was pushed above")
+    }
+
+    // The value returned by the native function, it must be owned by the call
+    fn result(mut self, result: *const Value) -> Result<Option<Value>, ErrorKind> {
+        if let Some(message) = self.error.take() {
+            return Err(ErrorKind::Native(message));
+        }
+
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        self.args
+            .iter()
+            .chain(self.values.iter().map(|value| &**value))
+            .find(|value| std::ptr::eq(*value, result))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| ErrorKind::Native("Returned a value the call doesn't own".to_string()))
+    }
+}
+
+pub(crate) fn call_native(
+    function: NativeFn,
+    args: Vec<Value>,
+) -> Result<Option<Value>, ErrorKind> {
+    let mut call = NativeCall {
+        args,
+        values: vec![],
+        error: None,
+    };
+
+    // The plugin was checked to implement this ABI version when it was loaded
+    let result = unsafe { function(&mut call) };
+
+    call.result(result)
+}
+
+// Functions registered by a plugin's `ms_plugin_init`
+#[derive(Default)]
+pub struct NativeRegistry {
+    pub fns: HashMap<String, NativeFn>,
+}
+
+unsafe fn string(string: *const c_char) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
+
+// Define the API table given to plugins along with the functions it points to. The
+// C header is generated from the same definitions
+macro_rules! api {
+    ($(#[doc = $doc:literal] fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? $body:block)*) => {
+        // Functions of the runtime plugins can call, passed to `ms_plugin_init`
+        #[repr(C)]
+        pub struct NativeApi {
+            pub version: u32,
+            $(pub $name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        $(unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? $body)*
+
+        pub static API: NativeApi = NativeApi {
+            version: ABI_VERSION,
+            $($name,)*
+        };
+
+        // Name, comment, return type and parameters of each function of the API
+        const API_FUNCTIONS: &[(&str, &str, &str, &[(&str, &str)])] = &[$((
+            stringify!($name),
+            $doc,
+            stringify!($($ret)?),
+            &[$((stringify!($arg), stringify!($ty))),*],
+        ),)*];
+    };
+}
+
+api! {
+    /// Number of arguments passed to the call
+    fn arg_count(call: *mut NativeCall) -> usize {
+        (&*call).args.len()
+    }
+
+    /// Argument at `index`, NULL past the last argument
+    fn arg(call: *mut NativeCall, index: usize) -> *const Value {
+        (&*call).args.get(index).map_or(std::ptr::null(), |value| value)
+    }
+
+    /// Type of a value, one of the MS_* types. NULL is MS_NULL
+    fn type_of(value: *const Value) -> ValueType {
+        value.as_ref().map_or(0, value_type)
+    }
+
+    /// Value of a bool, false for other types
+    fn as_bool(value: *const Value) -> bool {
+        matches!(value.as_ref(), Some(Value::Boolean(true)))
+    }
+
+    /// Value of an int, 0 for other types
+    fn as_int(value: *const Value) -> i32 {
+        match value.as_ref() {
+            Some(Value::Integer(i)) => *i,
+            _ => 0,
+        }
+    }
+
+    /// Value of an int or a long, 0 for other types
+    fn as_long(value: *const Value) -> i64 {
+        match value.as_ref() {
+            Some(Value::Integer(i)) => *i as i64,
+            Some(Value::Long(l)) => *l,
+            _ => 0,
+        }
+    }
+
+    /// Value of a float or a double, 0 for other types
+    fn as_double(value: *const Value) -> f64 {
+        match value.as_ref() {
+            Some(Value::Float(f)) => *f as f64,
+            Some(Value::Double(d)) => *d,
+            _ => 0.0,
+        }
+    }
+
+    /// UTF-8 bytes of a string, which are not NUL terminated, and their number in `length`. NULL for other types
+    fn as_string(value: *const Value, length: *mut usize) -> *const c_char {
+        let Some(Value::String(string)) = value.as_ref() else {
+            return std::ptr::null();
+        };
+
+        if !length.is_null() {
+            *length = string.len();
+        }
+
+        string.as_ptr() as *const c_char
+    }
+
+    /// New null value
+    fn new_null(call: *mut NativeCall) -> *const Value {
+        (&mut *call).value(Value::Null)
+    }
+
+    /// New bool value
+    fn new_bool(call: *mut NativeCall, value: bool) -> *const Value {
+        (&mut *call).value(Value::Boolean(value))
+    }
+
+    /// New int value
+    fn new_int(call: *mut NativeCall, value: i32) -> *const Value {
+        (&mut *call).value(Value::Integer(value))
+    }
+
+    /// New long value
+    fn new_long(call: *mut NativeCall, value: i64) -> *const Value {
+        (&mut *call).value(Value::Long(value))
+    }
+
+    /// New float value
+    fn new_float(call: *mut NativeCall, value: f32) -> *const Value {
+        (&mut *call).value(Value::Float(value))
+    }
+
+    /// New double value
+    fn new_double(call: *mut NativeCall, value: f64) -> *const Value {
+        (&mut *call).value(Value::Double(value))
+    }
+
+    /// New string from `length` bytes of UTF-8, invalid sequences are replaced
+    fn new_string(call: *mut NativeCall, bytes: *const c_char, length: usize) -> *const Value {
+        if bytes.is_null() {
+            return (&mut *call).value(Value::String(String::new()));
+        }
+
+        let bytes = std::slice::from_raw_parts(bytes as *const u8, length);
+
+        (&mut *call).value(Value::String(String::from_utf8_lossy(bytes).into_owned()))
+    }
+
+    /// Fail the call with a runtime error once the native function returns, its return value is ignored
+    fn raise(call: *mut NativeCall, message: *const c_char) {
+        (&mut *call).error = Some(string(message));
+    }
+
+    /// Register a function of the plugin, modules load it by name with (fn.get)
+    fn register_fn(registry: *mut NativeRegistry, name: *const c_char, function: NativeFn) {
+        (&mut *registry).fns.insert(string(name), function);
+    }
+}
+
+// C type of a Rust type of the API
+fn c_type(ty: &str) -> &'static str {
+    match ty.replace(' ', "").as_str() {
+        "" => "void",
+        "bool" => "bool",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "u32" => "uint32_t",
+        "f32" => "float",
+        "f64" => "double",
+        "usize" => "size_t",
+        "*mutusize" => "size_t *",
+        "*constc_char" => "const char *",
+        "*constValue" => "const MsValue *",
+        "*mutNativeCall" => "MsCall *",
+        "*mutNativeRegistry" => "MsRegistry *",
+        "ValueType" => "MsType",
+        "NativeFn" => "MsNativeFn",
+        ty => unreachable!("No C type for {}", ty),
+    }
+}
+
+// `type name`, without a space after pointer types
+fn c_declaration(ty: &str, name: &str) -> String {
+    let ty = c_type(ty);
+
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+// The C header plugins are built with, `ms header` prints it
+pub fn header() -> String {
+    let mut header = String::from(
+        "/* MintScript native plugin interface, generated by `ms header`. Do not edit. */\n\
+         \n\
+         #ifndef MINTSCRIPT_H\n\
+         #define MINTSCRIPT_H\n\
+         \n\
+         #include <stdbool.h>\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n\
+         \n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {\n\
+         #endif\n\
+         \n",
+    );
+
+    header += &format!("#define MS_ABI_VERSION {}\n\n", ABI_VERSION);
+
+    header += "typedef uint32_t MsType;\n\n";
+
+    for (code, name) in VALUE_TYPES.iter().enumerate() {
+        header += &format!("#define {} {}\n", name, code);
+    }
+
+    header += "\n\
+        /* A value, only valid until the native function it was given to or created in returns */\n\
+        typedef struct MsValue MsValue;\n\
+        /* A call of a native function */\n\
+        typedef struct MsCall MsCall;\n\
+        /* Functions registered by a plugin */\n\
+        typedef struct MsRegistry MsRegistry;\n\
+        \n\
+        /* A native function. Returns one of its arguments, a value created with the new_* functions or NULL to return nothing */\n\
+        typedef const MsValue *(*MsNativeFn)(MsCall *call);\n\
+        \n\
+        /* Functions of the runtime, passed to ms_plugin_init */\n\
+        typedef struct MsApi {\n    uint32_t version;\n";
+
+    for (name, doc, ret, params) in API_FUNCTIONS {
+        let params: Vec<String> = params
+            .iter()
+            .map(|(name, ty)| c_declaration(ty, name))
+            .collect();
+
+        header += &format!("    /* {} */\n", doc.trim());
+        header += &format!(
+            "    {})({});\n",
+            c_declaration(ret, &format!("(*{}", name)),
+            params.join(", ")
+        );
+    }
+
+    header += "} MsApi;\n\
+        \n\
+        #ifdef _WIN32\n\
+        #define MS_EXPORT __declspec(dllexport)\n\
+        #else\n\
+        #define MS_EXPORT __attribute__((visibility(\"default\")))\n\
+        #endif\n\
+        \n\
+        /* Exported by plugins. Returns MS_ABI_VERSION, the plugin is only loaded by runtimes implementing it */\n\
+        MS_EXPORT uint32_t ms_plugin_abi_version(void);\n\
+        /* Exported by plugins. Registers the plugin's functions, returns 0 on success */\n\
+        MS_EXPORT int32_t ms_plugin_init(const MsApi *api, MsRegistry *registry);\n\
+        \n\
+        #ifdef __cplusplus\n\
+        }\n\
+        #endif\n\
+        \n\
+        #endif\n";
+
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, load_modules, load_modules_from, VirtualMachine};
+
+    #[test]
+    fn header_is_up_to_date() {
+        assert_eq!(include_str!("../include/mintscript.h"), header());
+    }
+
+    // Build the example plugin with the system's C compiler
+    #[cfg(unix)]
+    fn build_example() -> String {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        let library = std::env::temp_dir().join(format!("ms-math-{}.so", std::process::id()));
+
+        let status = std::process::Command::new("gcc")
+            .args(["-shared", "-fPIC", "-Wall", "-Werror", "-I"])
+            .arg(format!("{}/include", manifest))
+            .arg(format!("{}/../examples/plugin/math.c", manifest))
+            .arg("-o")
+            .arg(&library)
+            .status()
+            .expect("gcc is needed to build the example plugin");
+
+        assert!(status.success());
+
+        library.to_string_lossy().into_owned()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_c_plugin() {
+        let library = build_example();
+
+        let (modules, dymodules) = load_modules(
+            &assemble(&format!(
+                "(mod.load \"{0}\" (fn.get add) (fn.get greet as hello) (fn.get identity))
+                (mod main
+                    (fn sum (i32.const 1) (i32.const 2) (call \"{0}\" add 2))
+                    (fn bad (str.const x) (call \"{0}\" add 1))
+                    (fn list (list.new) (call \"{0}\" identity 1))
+                )",
+                library
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        for dymodule in dymodules {
+            vm.add_dynamic_module(dymodule);
+        }
+
+        vm.link().unwrap();
+
+        assert!(matches!(
+            vm.call("main", "sum", vec![]),
+            Ok(Some(Value::Integer(3)))
+        ));
+        assert!(matches!(
+            vm.call(&library, "hello", vec![Value::String("ñu".to_string())]),
+            Ok(Some(Value::String(s))) if s == "Hello, ñu"
+        ));
+
+        let error = vm.call("main", "bad", vec![]).unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::Native("add expects two ints".to_string())
+        );
+        assert_eq!(error.function, "bad");
+
+        let list = vm.call("main", "list", vec![]).unwrap().unwrap();
+        assert_eq!(vm.type_of(&list), "list");
+
+        let missing =
+            load_modules(&assemble(&format!("(mod.load \"{}\" (fn.get sub))", library)).unwrap());
+        assert_eq!(
+            missing.err().unwrap(),
+            format!("{}: Function sub is not registered by the plugin", library)
+        );
+
+        // Found by name in a search path, the platform suffix is added
+        let path = std::path::Path::new(&library);
+        let name = path.file_stem().unwrap().to_string_lossy();
+
+        let (_, dymodules) = load_modules_from(
+            &assemble(&format!("(mod.load {} (fn.get add))", name)).unwrap(),
+            &[path.parent().unwrap().to_path_buf()],
+        )
+        .unwrap();
+        assert_eq!(dymodules[0].name, name);
+
+        // Replacing a native module with another library of the same name keeps the
+        // one linked calls point into loaded
+        let copy = std::env::temp_dir().join(format!("ms-replaced-{}", std::process::id()));
+        std::fs::create_dir_all(&copy).unwrap();
+        std::fs::copy(path, copy.join(path.file_name().unwrap())).unwrap();
+
+        let source = assemble(&format!(
+            "(mod.load \"{0}\" (fn.get add))
+            (mod main (fn sum (i32.const 1) (i32.const 2) (call \"{0}\" add 2)))",
+            name
+        ))
+        .unwrap();
+
+        let (modules, dymodules) = load_modules_from(&source, std::slice::from_ref(&copy)).unwrap();
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        for dymodule in dymodules {
+            vm.add_dynamic_module(dymodule);
+        }
+
+        vm.link().unwrap();
+
+        let (_, dymodules) =
+            load_modules_from(&source, &[path.parent().unwrap().to_path_buf()]).unwrap();
+
+        for dymodule in dymodules {
+            vm.add_dynamic_module(dymodule);
+        }
+
+        assert!(matches!(
+            vm.call("main", "sum", vec![]),
+            Ok(Some(Value::Integer(3)))
+        ));
+
+        std::fs::remove_dir_all(&copy).unwrap();
+        std::fs::remove_file(&library).unwrap();
+    }
+}
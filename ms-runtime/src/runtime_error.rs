@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use crate::{ByteCode, Frame};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    // An instruction needed more values than the stack holds
+    StackUnderflow,
+    // A single operand had the wrong type, e.g. a non boolean condition
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    // A binary operation is not defined for these operand types
+    InvalidOperands {
+        lhs: &'static str,
+        rhs: &'static str,
+    },
+    LocalNotFound(u32),
+    FieldNotFound(u32),
+    // Named property missing from a native object
+    PropertyNotFound(String),
+    // Method not provided by a native object
+    MethodNotFound(String),
+    // (upvalue.get) outside of a closure or past its captured values
+    UpvalueNotFound(u32),
+    ModuleNotFound(String),
+    // Qualified name of the function, `module.function`
+    FunctionNotFound(String),
+    // Qualified name of the global, `module.global`
+    GlobalNotFound(String),
+    // A function declaring its parameters with (fn.params) was called with a different number of arguments
+    ArityMismatch {
+        expected: u32,
+        found: u32,
+    },
+    // Integer arithmetic overflowed with `Overflow::Checked`
+    IntegerOverflow,
+    // Integer division or remainder by zero
+    DivisionByZero,
+    // List or string index outside of `0..len`
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    // (list.pop) on an empty list
+    EmptyList,
+    // Map key that isn't a string or an integer
+    InvalidKey(&'static str),
+    // Missing map key, formatted as a value
+    KeyNotFound(String),
+    // An operand of the right type with a value the instruction can't use
+    InvalidArgument(String),
+    // An allocation would grow the heap past its limit of live objects
+    HeapLimit(usize),
+    // A handle kept by the host outlived its object
+    ObjectFreed,
+    // Declarations such as (fn) or (mod) found inside a function body
+    InvalidInstruction,
+    // Error raised by a native function
+    Native(String),
+    // A value raised by (throw) that no (try) block caught, formatted as a value
+    Exception(String),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::StackUnderflow => write!(f, "Stack underflow"),
+            ErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "Expected {}, found {}", expected, found)
+            }
+            ErrorKind::InvalidOperands { lhs, rhs } => {
+                write!(f, "Invalid operand types {} and {}", lhs, rhs)
+            }
+            ErrorKind::LocalNotFound(index) => write!(f, "Local variable {} not found", index),
+            ErrorKind::FieldNotFound(index) => write!(f, "Field {} not found", index),
+            ErrorKind::PropertyNotFound(name) => write!(f, "Property \"{}\" not found", name),
+            ErrorKind::MethodNotFound(name) => write!(f, "Method \"{}\" not found", name),
+            ErrorKind::UpvalueNotFound(index) => write!(f, "Upvalue {} not found", index),
+            ErrorKind::ModuleNotFound(module) => write!(f, "Module \"{}\" not found", module),
+            ErrorKind::FunctionNotFound(name) => write!(f, "Function \"{}\" not found", name),
+            ErrorKind::GlobalNotFound(name) => write!(f, "Global \"{}\" not found", name),
+            ErrorKind::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} argument(s), found {}", expected, found)
+            }
+            ErrorKind::IntegerOverflow => write!(f, "Integer overflow"),
+            ErrorKind::DivisionByZero => write!(f, "Division by zero"),
+            ErrorKind::IndexOutOfBounds { index, len } => {
+                write!(f, "Index {} out of bounds for length {}", index, len)
+            }
+            ErrorKind::EmptyList => write!(f, "Pop from an empty list"),
+            ErrorKind::InvalidKey(found) => {
+                write!(f, "Expected a string or integer map key, found {}", found)
+            }
+            ErrorKind::KeyNotFound(key) => write!(f, "Key {} not found", key),
+            ErrorKind::InvalidArgument(message) => write!(f, "Invalid argument: {}", message),
+            ErrorKind::HeapLimit(limit) => write!(f, "Heap limit of {} objects exceeded", limit),
+            ErrorKind::ObjectFreed => write!(f, "Object was freed by the garbage collector"),
+            ErrorKind::InvalidInstruction => write!(f, "Instruction not allowed here"),
+            ErrorKind::Native(message) => write!(f, "Native function error: {}", message),
+            ErrorKind::Exception(value) => write!(f, "Uncaught exception {}", value),
+        }
+    }
+}
+
+// An error raised while executing code. `module` and `function` are empty when the
+// failing code was run directly through `VirtualMachine::execute`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub module: String,
+    pub function: String,
+    pub instruction: Option<ByteCode>,
+    // Call frames active when the error was raised, outermost first
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, instruction: Option<ByteCode>) -> RuntimeError {
+        RuntimeError {
+            kind,
+            module: String::new(),
+            function: String::new(),
+            instruction,
+            trace: vec![],
+        }
+    }
+
+    // Readable stack trace, most recent call last
+    pub fn traceback(&self) -> String {
+        let mut traceback = String::from("Traceback (most recent call last):");
+
+        for frame in self.trace.iter() {
+            traceback.push_str(&format!("\n  {}", frame));
+        }
+
+        traceback
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        if !self.function.is_empty() {
+            write!(f, " in {}.{}", self.module, self.function)?;
+        }
+
+        if let Some(instruction) = self.instruction {
+            write!(f, " at {:?}", instruction)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -0,0 +1,252 @@
+use crate::{ErrorKind, Instruction, Object, Value, VirtualMachine};
+
+// Longest string (str.repeat) builds, in bytes
+pub(crate) const MAX_STRING_LENGTH: usize = 1 << 30;
+
+// Byte offset of the character at `index`, the length of the string for the index
+// one past the last character
+fn byte_offset(string: &str, index: i64) -> Result<usize, ErrorKind> {
+    let out_of_bounds = || ErrorKind::IndexOutOfBounds {
+        index,
+        len: string.chars().count(),
+    };
+
+    let index = usize::try_from(index).map_err(|_| out_of_bounds())?;
+
+    string
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([string.len()])
+        .nth(index)
+        .ok_or_else(out_of_bounds)
+}
+
+// Characters `start..end` of a string, indexed by character rather than by byte
+pub(crate) fn substring(string: &str, start: i64, end: i64) -> Result<&str, ErrorKind> {
+    let start_offset = byte_offset(string, start)?;
+    let end_offset = byte_offset(string, end)?;
+
+    if start_offset > end_offset {
+        return Err(ErrorKind::InvalidArgument(format!(
+            "Substring start {} is after its end {}",
+            start, end
+        )));
+    }
+
+    Ok(&string[start_offset..end_offset])
+}
+
+// Character index of the first occurrence of `needle`, -1 if there is none
+pub(crate) fn index_of(string: &str, needle: &str) -> i64 {
+    match string.find(needle) {
+        Some(offset) => string[..offset].chars().count() as i64,
+        None => -1,
+    }
+}
+
+// An empty separator splits the string into its characters
+pub(crate) fn split(string: &str, separator: &str) -> Vec<Value> {
+    if separator.is_empty() {
+        return string
+            .chars()
+            .map(|c| Value::String(c.to_string()))
+            .collect();
+    }
+
+    string
+        .split(separator)
+        .map(|part| Value::String(part.to_string()))
+        .collect()
+}
+
+impl VirtualMachine {
+    fn pop_string(&mut self) -> Result<String, ErrorKind> {
+        match self.pop()? {
+            Value::String(string) => Ok(string),
+            value => Err(ErrorKind::TypeMismatch {
+                expected: "string",
+                found: value.type_name(),
+            }),
+        }
+    }
+
+    // Execute a (str.*) instruction. Lengths and indices count Unicode scalar values
+    pub(crate) fn string_op(&mut self, instruction: &Instruction) -> Result<(), ErrorKind> {
+        let value = match instruction {
+            Instruction::StringLength => Value::Integer(self.pop_string()?.chars().count() as i32),
+            Instruction::StringSubstring => {
+                let end = self.pop_index()?;
+                let start = self.pop_index()?;
+                let string = self.pop_string()?;
+
+                Value::String(substring(&string, start, end)?.to_string())
+            }
+            Instruction::StringIndexOf => {
+                let needle = self.pop_string()?;
+                let string = self.pop_string()?;
+
+                Value::Integer(index_of(&string, &needle) as i32)
+            }
+            Instruction::StringSplit => {
+                let separator = self.pop_string()?;
+                let string = self.pop_string()?;
+
+                self.allocate(Object::List(split(&string, &separator)))?
+            }
+            Instruction::StringTrim => Value::String(self.pop_string()?.trim().to_string()),
+            Instruction::StringUpper => Value::String(self.pop_string()?.to_uppercase()),
+            Instruction::StringLower => Value::String(self.pop_string()?.to_lowercase()),
+            Instruction::StringCharCode => {
+                let index = self.pop_index()?;
+                let string = self.pop_string()?;
+                let offset = byte_offset(&string, index)?;
+
+                let Some(char) = string[offset..].chars().next() else {
+                    return Err(ErrorKind::IndexOutOfBounds {
+                        index,
+                        len: string.chars().count(),
+                    });
+                };
+
+                Value::Integer(char as i32)
+            }
+            Instruction::StringFromCharCode => {
+                let code = self.pop_index()?;
+
+                let Some(char) = u32::try_from(code).ok().and_then(char::from_u32) else {
+                    return Err(ErrorKind::InvalidArgument(format!(
+                        "{} is not a character code",
+                        code
+                    )));
+                };
+
+                Value::String(char.to_string())
+            }
+            Instruction::StringRepeat => {
+                let count = self.pop_index()?;
+                let string = self.pop_string()?;
+
+                let Ok(count) = usize::try_from(count) else {
+                    return Err(ErrorKind::InvalidArgument(format!(
+                        "Negative repeat count {}",
+                        count
+                    )));
+                };
+
+                if string.is_empty() || count == 0 {
+                    Value::String(String::new())
+                } else {
+                    // `repeat` aborts the process when the result can't be allocated
+                    let fits = string
+                        .len()
+                        .checked_mul(count)
+                        .is_some_and(|length| length <= MAX_STRING_LENGTH);
+
+                    if !fits {
+                        return Err(ErrorKind::InvalidArgument(format!(
+                            "Repeating a string of {} bytes {} times exceeds the maximum length of {} bytes",
+                            string.len(),
+                            count,
+                            MAX_STRING_LENGTH
+                        )));
+                    }
+
+                    Value::String(string.repeat(count))
+                }
+            }
+            _ => return Err(ErrorKind::InvalidInstruction),
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, load_modules};
+
+    #[test]
+    fn string_helpers_count_chars() {
+        assert_eq!(substring("añb😀c", 1, 4), Ok("ñb😀"));
+        assert_eq!(substring("añb", 3, 3), Ok(""));
+        assert_eq!(
+            substring("añb", 1, 4),
+            Err(ErrorKind::IndexOutOfBounds { index: 4, len: 3 })
+        );
+        assert!(substring("añb", 2, 1).is_err());
+        assert_eq!(index_of("ñañb", "ñb"), 2);
+        assert_eq!(index_of("ñañb", "x"), -1);
+        assert_eq!(split("ñ😀", "").len(), 2);
+    }
+
+    #[test]
+    fn string_instructions() {
+        let (modules, _) = load_modules(
+            &assemble(
+                "(mod main
+                    (fn len (str.const \"héllo\") (str.len))
+                    (fn split (str.const \" a,b,,c \") (str.trim) (str.const \",\") (str.split))
+                    (fn upper (str.const straße) (str.upper))
+                    (fn code (str.const a😀) (i32.const 1) (str.code))
+                    (fn char (i32.const 241) (str.from_code) (i32.const 3) (str.repeat))
+                    (fn bad_char (i32.const 55296) (str.from_code))
+                    (fn huge (str.const ab) (i64.const 9223372036854775807) (str.repeat))
+                    (fn empty (str.const a) (i32.const 0) (i32.const 0) (str.sub)
+                        (i64.const 9223372036854775807) (str.repeat))
+                    (fn too_long (str.const ab) (i64.const 1073741824) (str.repeat))
+                )",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        assert!(matches!(
+            vm.call("main", "len", vec![]),
+            Ok(Some(Value::Integer(5)))
+        ));
+
+        let list = vm.call("main", "split", vec![]).unwrap().unwrap();
+        assert_eq!(
+            format!("{:?}", vm.heap.debug(&list)),
+            "[\"a\", \"b\", \"\", \"c\"]"
+        );
+
+        assert!(matches!(
+            vm.call("main", "upper", vec![]),
+            Ok(Some(Value::String(s))) if s == "STRASSE"
+        ));
+        assert!(matches!(
+            vm.call("main", "code", vec![]),
+            Ok(Some(Value::Integer(0x1F600)))
+        ));
+        assert!(matches!(
+            vm.call("main", "char", vec![]),
+            Ok(Some(Value::String(s))) if s == "ñññ"
+        ));
+        assert!(matches!(
+            vm.call("main", "bad_char", vec![]).unwrap_err().kind,
+            ErrorKind::InvalidArgument(_)
+        ));
+        assert!(matches!(
+            vm.call("main", "huge", vec![]).unwrap_err().kind,
+            ErrorKind::InvalidArgument(_)
+        ));
+        assert!(matches!(
+            vm.call("main", "empty", vec![]),
+            Ok(Some(Value::String(s))) if s.is_empty()
+        ));
+        assert!(matches!(
+            vm.call("main", "too_long", vec![]).unwrap_err().kind,
+            ErrorKind::InvalidArgument(_)
+        ));
+    }
+}
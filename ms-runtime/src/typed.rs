@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+
+use crate::{ErrorKind, MapKey, Object, RuntimeError, Value, VirtualMachine};
+
+// Conversion of a Rust value to a script value, objects are allocated in the VM's heap
+pub trait ToValue {
+    fn to_value(self, vm: &mut VirtualMachine) -> Result<Value, ErrorKind>;
+}
+
+// Conversion of a script value to a Rust value, objects are read from the VM's heap
+pub trait FromValue: Sized {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind>;
+}
+
+fn mismatch(expected: &'static str, value: &Value, vm: &VirtualMachine) -> ErrorKind {
+    ErrorKind::TypeMismatch {
+        expected,
+        found: vm.type_of(value),
+    }
+}
+
+// Builds an object out of converted values. Values already converted are kept on the
+// stack until the object is allocated, so converting the next one can't free them
+pub struct ObjectBuilder<'a> {
+    vm: &'a mut VirtualMachine,
+    base: usize,
+}
+
+impl<'a> ObjectBuilder<'a> {
+    pub fn new(vm: &'a mut VirtualMachine) -> ObjectBuilder<'a> {
+        let base = vm.stack.len();
+
+        ObjectBuilder { vm, base }
+    }
+
+    pub fn push(&mut self, value: impl ToValue) -> Result<(), ErrorKind> {
+        let value = value.to_value(self.vm)?;
+        self.vm.stack.push(value);
+
+        Ok(())
+    }
+
+    // Allocate the object made of the values pushed, in order, e.g. `Object::List`
+    pub fn finish(self, object: impl FnOnce(Vec<Value>) -> Object) -> Result<Value, ErrorKind> {
+        // Allocated while the values are still on the stack, allocating may collect garbage
+        let value = self.vm.allocate(Object::Values(vec![]))?;
+        let values = self.vm.stack.split_off(self.base);
+
+        if let Some(slot) = value
+            .handle()
+            .and_then(|handle| self.vm.heap.get_mut(handle))
+        {
+            *slot = object(values);
+        }
+
+        Ok(value)
+    }
+}
+
+impl Drop for ObjectBuilder<'_> {
+    // Values left by a conversion that failed
+    fn drop(&mut self) {
+        self.vm.stack.truncate(self.base);
+    }
+}
+
+// Fields of a script object, for `FromValue` implementations of structs
+pub fn object_fields<'a>(
+    value: &Value,
+    vm: &'a VirtualMachine,
+    count: usize,
+) -> Result<&'a [Value], ErrorKind> {
+    let Some(Object::Values(fields)) = value.handle().and_then(|handle| vm.heap.get(handle)) else {
+        return Err(mismatch("object", value, vm));
+    };
+
+    if fields.len() < count {
+        return Err(ErrorKind::FieldNotFound(fields.len() as u32));
+    }
+
+    Ok(fields)
+}
+
+impl ToValue for Value {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(self)
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value, _: &VirtualMachine) -> Result<Self, ErrorKind> {
+        Ok(value)
+    }
+}
+
+impl ToValue for () {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::Null)
+    }
+}
+
+// Discards the value, for functions called for their effects
+impl FromValue for () {
+    fn from_value(_: Value, _: &VirtualMachine) -> Result<Self, ErrorKind> {
+        Ok(())
+    }
+}
+
+// Rust types stored as the value variant `$variant`, read back from the variants
+// listed after it, e.g. an i64 from an int or a long
+macro_rules! primitive {
+    ($($ty:ty => $variant:ident($as:ty), $name:literal, [$($from:ident),*];)*) => {$(
+        impl ToValue for $ty {
+            fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+                Ok(Value::$variant(self as $as))
+            }
+        }
+
+        impl FromValue for $ty {
+            fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+                match value {
+                    $(Value::$from(value) => <$ty>::try_from(value).map_err(|_| {
+                        ErrorKind::InvalidArgument(format!("{} doesn't fit {}", value, $name))
+                    }),)*
+                    _ => Err(mismatch($name, &value, vm)),
+                }
+            }
+        }
+    )*};
+}
+
+primitive! {
+    i8 => Integer(i32), "i8", [Integer, Long];
+    i16 => Integer(i32), "i16", [Integer, Long];
+    i32 => Integer(i32), "int", [Integer, Long];
+    u8 => Integer(i32), "u8", [Integer, Long];
+    u16 => Integer(i32), "u16", [Integer, Long];
+    u32 => Long(i64), "u32", [Integer, Long];
+    i64 => Long(i64), "long", [Integer, Long];
+}
+
+impl ToValue for f32 {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::Float(self))
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        match value {
+            Value::Float(f) => Ok(f),
+            _ => Err(mismatch("float", &value, vm)),
+        }
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::Double(self))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        match value {
+            Value::Float(f) => Ok(f as f64),
+            Value::Double(d) => Ok(d),
+            _ => Err(mismatch("double", &value, vm)),
+        }
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::Boolean(self))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(mismatch("bool", &value, vm)),
+        }
+    }
+}
+
+impl ToValue for String {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::String(self))
+    }
+}
+
+impl ToValue for &str {
+    fn to_value(self, _: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        Ok(Value::String(self.to_string()))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        match value {
+            Value::String(s) => Ok(s),
+            _ => Err(mismatch("string", &value, vm)),
+        }
+    }
+}
+
+// None is null
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(self, vm: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        match self {
+            Some(value) => value.to_value(vm),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value, vm).map(Some),
+        }
+    }
+}
+
+// A list
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(self, vm: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        let mut list = ObjectBuilder::new(vm);
+
+        for item in self {
+            list.push(item)?;
+        }
+
+        list.finish(Object::List)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        let Some(Object::List(items)) = value.handle().and_then(|handle| vm.heap.get(handle))
+        else {
+            return Err(mismatch("list", &value, vm));
+        };
+
+        items
+            .iter()
+            .map(|item| T::from_value(item.clone(), vm))
+            .collect()
+    }
+}
+
+// A map with string keys
+impl<T: ToValue> ToValue for HashMap<String, T> {
+    fn to_value(self, vm: &mut VirtualMachine) -> Result<Value, ErrorKind> {
+        let mut entries = ObjectBuilder::new(vm);
+        let mut keys = Vec::with_capacity(self.len());
+
+        for (key, value) in self {
+            entries.push(value)?;
+            keys.push(key);
+        }
+
+        entries.finish(|values| {
+            Object::Map(
+                keys.into_iter()
+                    .zip(values)
+                    .map(|(key, value)| (MapKey::String(key.clone()), (Value::String(key), value)))
+                    .collect(),
+            )
+        })
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value, vm: &VirtualMachine) -> Result<Self, ErrorKind> {
+        let Some(Object::Map(entries)) = value.handle().and_then(|handle| vm.heap.get(handle))
+        else {
+            return Err(mismatch("map", &value, vm));
+        };
+
+        entries
+            .values()
+            .map(|(key, value)| {
+                let key = String::from_value(key.clone(), vm)?;
+
+                Ok((key, T::from_value(value.clone(), vm)?))
+            })
+            .collect()
+    }
+}
+
+// Arguments of a typed call, a tuple of values implementing `ToValue`
+pub trait ToArgs {
+    fn to_args(self, vm: &mut VirtualMachine) -> Result<Vec<Value>, ErrorKind>;
+}
+
+macro_rules! args {
+    ($($arg:ident),*) => {
+        impl<$($arg: ToValue),*> ToArgs for ($($arg,)*) {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn to_args(self, vm: &mut VirtualMachine) -> Result<Vec<Value>, ErrorKind> {
+                let ($($arg,)*) = self;
+                let mut args = ObjectBuilder::new(vm);
+
+                $(args.push($arg)?;)*
+
+                // Taken off the stack, the builder has nothing left to clean up
+                Ok(args.vm.stack.split_off(args.base))
+            }
+        }
+    };
+}
+
+args!();
+args!(A);
+args!(A, B);
+args!(A, B, C);
+args!(A, B, C, D);
+args!(A, B, C, D, E);
+args!(A, B, C, D, E, F);
+args!(A, B, C, D, E, F, G);
+args!(A, B, C, D, E, F, G, H);
+
+impl VirtualMachine {
+    // Call a function converting its arguments from and its result to Rust values,
+    // `vm.call_typed::<(i32, String), bool>("main", "check", (1, "a".to_string()))`.
+    // A function that returns nothing returns null
+    pub fn call_typed<A: ToArgs, R: FromValue>(
+        &mut self,
+        module: &str,
+        name: &str,
+        args: A,
+    ) -> Result<R, RuntimeError> {
+        let args = args
+            .to_args(self)
+            .map_err(|kind| RuntimeError::new(kind, None))?;
+
+        let result = self.call(module, name, args)?.unwrap_or(Value::Null);
+
+        R::from_value(result, self).map_err(|kind| RuntimeError::new(kind, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, load_modules};
+    use ms_derive::{FromValue, ToValue};
+
+    #[derive(Debug, PartialEq, ToValue, FromValue)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq, ToValue, FromValue)]
+    struct Tagged<T>(String, Vec<T>);
+
+    #[test]
+    fn value_conversions() {
+        let mut vm = VirtualMachine::new();
+        vm.heap.set_threshold(1);
+
+        let value = vec![Some(Point { x: 1, y: 2 }), None]
+            .to_value(&mut vm)
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", vm.heap.debug(&value)),
+            "[Object[1, 2], Null]"
+        );
+        assert_eq!(
+            Vec::<Option<Point>>::from_value(value, &vm).unwrap(),
+            vec![Some(Point { x: 1, y: 2 }), None]
+        );
+
+        let tagged = Tagged("a".to_string(), vec![1.5f64]);
+        let value = tagged.to_value(&mut vm).unwrap();
+        assert_eq!(
+            Tagged::from_value(value, &vm).unwrap(),
+            Tagged("a".to_string(), vec![1.5])
+        );
+
+        let map = HashMap::from([("a".to_string(), vec![1u8]), ("b".to_string(), vec![])]);
+        let value = map.clone().to_value(&mut vm).unwrap();
+        assert_eq!(
+            format!("{:?}", vm.heap.debug(&value)),
+            "{\"a\": [1], \"b\": []}"
+        );
+        assert_eq!(HashMap::from_value(value, &vm), Ok(map));
+
+        assert_eq!(i64::from_value(Value::Integer(3), &vm), Ok(3));
+        assert!(u8::from_value(Value::Integer(256), &vm).is_err());
+        assert_eq!(
+            String::from_value(Value::Integer(1), &vm),
+            Err(ErrorKind::TypeMismatch {
+                expected: "string",
+                found: "int"
+            })
+        );
+        assert_eq!(vm.stack.len(), 0);
+    }
+
+    #[test]
+    fn typed_calls() {
+        let (modules, _) = load_modules(
+            &assemble(
+                "(mod main
+                    (fn add (fn.params 2) (local.get 0) (local.get 1) (op.add))
+                    (fn swap (fn.params 1)
+                        (alloc 2)
+                        (local.get 0) (field.get 1) (field.set 0)
+                        (local.get 0) (field.get 0) (field.set 1))
+                    (fn nothing)
+                )",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        assert_eq!(
+            vm.call_typed::<(i32, i32), i32>("main", "add", (1, 2)),
+            Ok(3)
+        );
+        assert_eq!(
+            vm.call_typed::<_, String>("main", "add", ("a", "b".to_string())),
+            Ok("ab".to_string())
+        );
+        assert_eq!(
+            vm.call_typed::<_, Point>("main", "swap", (Point { x: 1, y: 2 },)),
+            Ok(Point { x: 2, y: 1 })
+        );
+        assert_eq!(vm.call_typed::<(), ()>("main", "nothing", ()), Ok(()));
+        assert_eq!(
+            vm.call_typed::<(), Option<i32>>("main", "nothing", ()),
+            Ok(None)
+        );
+        assert!(vm.call_typed::<_, bool>("main", "add", (1, 2)).is_err());
+    }
+}
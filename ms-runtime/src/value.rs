@@ -1,24 +1,141 @@
-use std::{
-    fmt::Debug,
-    sync::{Arc, Mutex},
-};
+use std::{any::Any, collections::BTreeMap, fmt::Debug};
+
+use crate::{virtual_machine::Callee, ErrorKind, Handle};
 
 pub enum Object {
     Values(Vec<Value>),
+    List(Vec<Value>),
+    // Entries keep the key value they were first inserted with, `map.keys` returns it
+    Map(BTreeMap<MapKey, (Value, Value)>),
+    Function(Closure),
     Native(Box<dyn NativeObject>),
 }
 
+// A function value, the function along with the values it captured when it was created
+pub struct Closure {
+    pub module: String,
+    pub name: String,
+    pub upvalues: Vec<Value>,
+    pub(crate) callee: Callee,
+}
+
 #[derive(Clone)]
 pub enum Value {
     Null,
     Boolean(bool),
     Integer(i32),
     Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    // Objects live in the VM's heap, see `Heap::debug` to print their contents
+    Object(Handle),
+}
+
+// A Rust value owned by the VM's heap. Scripts read and write its properties with
+// (prop.get), (prop.set), (field.get) and (field.set), and call its methods with
+// (call.method). Every operation fails by default
+pub trait NativeObject: Any {
+    // Name printed by `Debug` and returned by (type.of)
+    fn type_name(&self) -> &'static str {
+        "native object"
+    }
+
+    // Property `key`, a name for (prop.get) and an index for (field.get)
+    fn get(&self, key: &MapKey) -> Result<Value, ErrorKind> {
+        Err(property_not_found(key))
+    }
+
+    fn set(&mut self, key: &MapKey, value: Value) -> Result<(), ErrorKind> {
+        let _ = value;
+        Err(property_not_found(key))
+    }
+
+    // Method called by (call.method), returning None pushes nothing
+    fn call(&mut self, method: &str, args: Vec<Value>) -> Result<Option<Value>, ErrorKind> {
+        let _ = args;
+        Err(ErrorKind::MethodNotFound(method.to_string()))
+    }
+
+    // Values held by the object, they are kept alive as long as the object is
+    fn trace(&self) -> Vec<Value> {
+        vec![]
+    }
+
+    // Called once when the object is freed by the garbage collector or the VM is dropped
+    fn finalize(&mut self) {}
+}
+
+fn property_not_found(key: &MapKey) -> ErrorKind {
+    match key {
+        MapKey::Integer(index) => ErrorKind::FieldNotFound(*index as u32),
+        MapKey::String(name) => ErrorKind::PropertyNotFound(name.clone()),
+    }
+}
+
+impl dyn NativeObject {
+    pub fn downcast_ref<T: NativeObject>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: NativeObject>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+}
+
+// Key of a map entry. Integer widths are merged so keys compare like `cmp.eq`, and
+// the ordering gives maps a deterministic iteration order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Integer(i64),
     String(String),
-    Object(Arc<Mutex<Object>>),
 }
 
-pub trait NativeObject {}
+impl MapKey {
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::Integer(i) => Some(MapKey::Integer(*i as i64)),
+            Value::Long(l) => Some(MapKey::Integer(*l)),
+            Value::String(s) => Some(MapKey::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Values(_) => "object",
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+            Object::Function(_) => "function",
+            Object::Native(native) => native.type_name(),
+        }
+    }
+}
+
+impl Value {
+    // Name of the value's type as used in runtime error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "bool",
+            Value::Integer(_) => "int",
+            Value::Float(_) => "float",
+            Value::Long(_) => "long",
+            Value::Double(_) => "double",
+            Value::String(_) => "string",
+            Value::Object(_) => "object",
+        }
+    }
+
+    pub fn handle(&self) -> Option<Handle> {
+        match self {
+            Value::Object(handle) => Some(*handle),
+            _ => None,
+        }
+    }
+}
 
 impl Debug for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -38,7 +155,13 @@ impl Debug for Object {
 
                 write!(f, "]")
             }
-            Object::Native(_) => write!(f, "Native"),
+            Object::List(values) => write!(f, "List{:?}", values),
+            Object::Map(entries) => f
+                .debug_map()
+                .entries(entries.values().map(|(k, v)| (k, v)))
+                .finish(),
+            Object::Function(closure) => write!(f, "fn {}.{}", closure.module, closure.name),
+            Object::Native(native) => write!(f, "{}", native.type_name()),
         }
     }
 }
@@ -50,11 +173,10 @@ impl Debug for Value {
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Integer(i) => write!(f, "{}", i),
             Value::Float(fl) => write!(f, "{}", fl),
+            Value::Long(l) => write!(f, "{}", l),
+            Value::Double(d) => write!(f, "{}", d),
             Value::String(s) => write!(f, "\"{}\"", s),
-            Value::Object(arc) => {
-                let obj = arc.lock().unwrap();
-                write!(f, "Object{:?}", obj)
-            }
+            Value::Object(handle) => write!(f, "Object{}", handle),
         }
     }
 }
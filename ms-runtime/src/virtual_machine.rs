@@ -1,23 +1,89 @@
-use core::panic;
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
 };
 
+use libloading::Library;
+
 use crate::{
+    arithmetic::{float_modulo, Op},
     instruction::{Code, Instruction},
+    lower,
     module::Module,
-    DyModule, Function, Object, Value,
+    plugin::call_native,
+    ByteCode, Closure, DyModule, ErrorKind, Frame, Function, Heap, MapKey, NativeFn, Object,
+    Overflow, RuntimeError, Value,
 };
 
+// A function being executed, the VM keeps one per active call for stack traces
+struct CallFrame {
+    function: Rc<Function>,
+    // Index of the instruction being executed in the function's lowered code
+    instruction: usize,
+    // Source line from the (line) debug info, if the code has any
+    line: Option<u32>,
+    // Function value the function was called through, (upvalue.get) reads its upvalues
+    closure: Option<Value>,
+}
+
+impl CallFrame {
+    fn to_frame(&self) -> Frame {
+        Frame {
+            module: self.function.module.clone(),
+            function: self.function.name.clone(),
+            instruction: self.instruction,
+            line: self.line,
+        }
+    }
+}
+
+// A (try.start) of the code being run, with the depths to unwind to when its
+// handler catches an error
+struct Handler {
+    target: usize,
+    stack: usize,
+    locals: usize,
+    frames: usize,
+}
+
+// A function of the host registered with `VirtualMachine::register_native`
+pub type HostFn = Rc<dyn Fn(&mut VirtualMachine, Vec<Value>) -> Result<Option<Value>, ErrorKind>>;
+
+// A resolved call target
+#[derive(Clone)]
+pub(crate) enum Callee {
+    // The library is kept loaded while a call can reach the function, even when its
+    // module is replaced
+    Native(Rc<Library>, NativeFn),
+    Host(HostFn),
+    Script(Rc<Function>),
+}
+
+fn invalid_operands(lhs: &Value, rhs: &Value) -> ErrorKind {
+    ErrorKind::InvalidOperands {
+        lhs: lhs.type_name(),
+        rhs: rhs.type_name(),
+    }
+}
+
 pub struct VirtualMachine {
     pub stack: Vec<Value>,
     pub modules: HashMap<String, Module>,
     pub dymodules: HashMap<String, DyModule>,
+    // Host functions by module and name
+    pub(crate) natives: HashMap<String, HashMap<String, HostFn>>,
+    // Arguments of the running host functions, kept alive while they allocate
+    native_args: Vec<Value>,
     pub local_vars: Vec<Vec<Value>>,
-    pub call_break: bool,
-    pub call_continue: bool,
-    pub call_return: bool,
+    pub heap: Heap,
+    // Behavior of integer arithmetic that overflows
+    pub overflow: Overflow,
+    frames: Vec<CallFrame>,
+    // Function table that linked (invoke) instructions index into
+    pub(crate) functions: Vec<Callee>,
+    pub(crate) links: HashMap<(String, String), u32>,
+    // Value raised by the last (throw), until a handler catches it
+    exception: Option<Value>,
 }
 
 impl<'a> VirtualMachine {
@@ -26,10 +92,15 @@ impl<'a> VirtualMachine {
             stack: Vec::new(),
             modules: HashMap::new(),
             dymodules: HashMap::new(),
+            natives: HashMap::new(),
+            native_args: Vec::new(),
             local_vars: Vec::new(),
-            call_break: false,
-            call_continue: false,
-            call_return: false,
+            heap: Heap::new(),
+            overflow: Overflow::default(),
+            frames: Vec::new(),
+            functions: Vec::new(),
+            links: HashMap::new(),
+            exception: None,
         }
     }
 
@@ -41,499 +112,1145 @@ impl<'a> VirtualMachine {
         self.dymodules.insert(module.name.clone(), module);
     }
 
-    pub fn execute(&mut self, code: &'a Code) {
-        self.call_break = false;
-        self.call_continue = false;
-        self.call_return = false;
+    // Make a host function callable from scripts as `module.name`, through (call) like
+    // any other function. It receives the VM, e.g. to allocate objects or to call back
+    // into scripts, and the call's arguments
+    pub fn register_native<F>(&mut self, module: &str, name: &str, function: F)
+    where
+        F: Fn(&mut VirtualMachine, Vec<Value>) -> Result<Option<Value>, ErrorKind> + 'static,
+    {
+        let function: HostFn = Rc::new(function);
 
-        for instruction in code.iter() {
-            match instruction {
-                Instruction::None => {}
-                Instruction::Version {
-                    major: _,
-                    minor: _,
-                    patch: _,
-                } => {}
-                Instruction::Dump => {
-                    println!("Stack: {:?}", self.stack);
-                    println!("Locals: {:?}", self.local_vars);
-                }
-                Instruction::Hi => {
-                    println!("Hi!");
-                }
-                Instruction::Fn { name: _, code: _ } => {
-                    panic!("Function declaration not allowed here");
-                }
-                Instruction::Call {
-                    module,
-                    function,
-                    param_count,
-                } => {
-                    let args = self
-                        .stack
-                        .split_off(self.stack.len() - *param_count as usize);
-
-                    self.call(module, function, args);
-
-                    self.call_return = false;
-                    self.call_continue = false;
-                    self.call_break = false;
-                }
-                Instruction::PushConstString { value } => {
-                    self.stack.push(Value::String(value.clone()));
-                }
-                Instruction::PushConstInteger { value } => {
-                    self.stack.push(Value::Integer(*value));
-                }
-                Instruction::PushConstFloat { value } => {
-                    self.stack.push(Value::Float(*value));
-                }
-                Instruction::PushConstBoolean { value } => {
-                    self.stack.push(Value::Boolean(*value));
-                }
-                Instruction::GetLocal { index } => {
-                    if let Some(locals) = self.local_vars.last() {
-                        self.stack.push(locals[*index as usize].clone());
-                    } else {
-                        panic!("Local variable not found");
-                    }
-                }
-                Instruction::SetLocal { index } => {
-                    if let Some(value) = self.stack.pop() {
-                        if let Some(locals) = self.local_vars.last_mut() {
-                            locals[*index as usize] = value;
-                        } else {
-                            panic!("Local variable not found");
-                        }
-                    } else {
-                        panic!("Local variable not found");
-                    }
-                }
-                Instruction::ReserveLocal { size } => {
-                    if let Some(locals) = self.local_vars.last_mut() {
-                        locals.resize(*size as usize, Value::Null);
-                    } else {
-                        panic!("Local variable not found");
-                    }
-                }
-                Instruction::Allocate { fields } => {
-                    let fields = vec![Value::Null; *fields as usize];
-                    self.stack
-                        .push(Value::Object(Arc::new(Mutex::new(Object::Values(fields)))));
-                }
-                Instruction::GetField { index } => {
-                    let Some(object) = self.stack.pop() else {
-                        panic!("No elements in the stack expected an object");
-                    };
+        // Code linked before keeps calling the function it was linked to otherwise
+        if let Some(index) = self.links.get(&(module.to_string(), name.to_string())) {
+            self.functions[*index as usize] = Callee::Host(function.clone());
+        }
 
-                    if let Value::Object(object) = object {
-                        let lock = object.lock();
-                        let object = lock.as_deref().unwrap();
-
-                        if let Object::Values(fields) = object {
-                            if let Some(value) = fields.get(*index as usize) {
-                                self.stack.push(value.clone());
-                            } else {
-                                panic!("Field not found");
-                            }
-                        } else {
-                            panic!("Expected an object with fields");
-                        }
-                    } else {
-                        panic!("Expected an object");
-                    }
-                }
-                Instruction::SetField { index } => {
-                    let Some(value) = self.stack.pop() else {
-                        panic!("No elements in the stack expected a value");
-                    };
-                    let Some(object) = self.stack.last() else {
-                        panic!("No elements in the stack expected an object");
-                    };
+        self.natives
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string(), function);
+    }
 
-                    if let Value::Object(object) = object {
-                        let mut lock = object.lock();
-                        let object = lock.as_deref_mut().unwrap();
-
-                        match object {
-                            Object::Values(fields) => {
-                                if let Some(_) = fields.get(*index as usize) {
-                                    fields[*index as usize] = value;
-                                } else {
-                                    panic!("Field not found");
-                                }
-                            }
-                            _ => {
-                                panic!("Expected an object with fields");
-                            }
-                        }
-                    } else {
-                        panic!("Expected an object");
-                    }
-                }
-                Instruction::Pop => {
-                    self.stack.pop();
-                }
-                Instruction::Dup => {
-                    if let Some(arg) = self.stack.last() {
-                        self.stack.push(arg.clone());
-                    } else {
-                        panic!("No elements in the stack");
-                    }
+    // Execute a block of code, returns the value it leaves on top of the stack
+    pub fn execute(&mut self, code: &'a Code) -> Result<Option<Value>, RuntimeError> {
+        let depth = (self.stack.len(), self.local_vars.len(), self.frames.len());
+
+        let result = self.run(&lower(code));
+
+        self.finish(depth, result)
+    }
+
+    // Call a function, returns its return value if it has one
+    pub fn call(
+        &mut self,
+        module: &str,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let depth = (self.stack.len(), self.local_vars.len(), self.frames.len());
+
+        let result = match self.resolve(module, name) {
+            Ok(callee) => self.invoke(callee, args, None),
+            Err(kind) => Err(RuntimeError::new(kind, None)),
+        };
+
+        self.finish(depth, result)
+    }
+
+    // Call a function value, e.g. a callback returned by script code
+    pub fn call_function(
+        &mut self,
+        function: &Value,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let depth = (self.stack.len(), self.local_vars.len(), self.frames.len());
+
+        let result = match self.closure(function) {
+            Ok(callee) => self.invoke(callee, args, Some(function.clone())),
+            Err(kind) => Err(RuntimeError::new(kind, None)),
+        };
+
+        self.finish(depth, result)
+    }
+
+    // Take the result of a host level execute or call off the stack and restore the
+    // state the VM was in before it, also after an error so the VM can be reused
+    fn finish(
+        &mut self,
+        (base, locals, frames): (usize, usize, usize),
+        result: Result<(), RuntimeError>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let value = match result {
+            Ok(_) if self.stack.len() > base => self.stack.pop(),
+            _ => None,
+        };
+
+        self.stack.truncate(base);
+        self.local_vars.truncate(locals);
+        self.frames.truncate(frames);
+
+        // A nested call made by a host function leaves the thrown value to the (try)
+        // of the script that called the host function
+        if frames == 0 {
+            self.exception = None;
+        }
+
+        result.map(|_| value)
+    }
+
+    // Host functions take precedence over plugin functions and those over script
+    // functions of the same name, as in the link table `link` builds
+    fn resolve(&self, module: &str, name: &str) -> Result<Callee, ErrorKind> {
+        if let Some(function) = self
+            .natives
+            .get(module)
+            .and_then(|natives| natives.get(name))
+        {
+            return Ok(Callee::Host(function.clone()));
+        }
+
+        if let Some(dymodule) = self.dymodules.get(module) {
+            if let Some(function) = dymodule.fns.get(name) {
+                return Ok(Callee::Native(dymodule.lib.clone(), *function));
+            }
+        }
+
+        if let Some(function) = self
+            .modules
+            .get(module)
+            .and_then(|module| module.get_shared_function(name))
+        {
+            return Ok(Callee::Script(function.clone()));
+        }
+
+        if self.natives.contains_key(module)
+            || self.dymodules.contains_key(module)
+            || self.modules.contains_key(module)
+        {
+            return Err(ErrorKind::FunctionNotFound(format!("{}.{}", module, name)));
+        }
+
+        Err(ErrorKind::ModuleNotFound(module.to_string()))
+    }
+
+    fn call_target(&self, instruction: &Instruction) -> Result<Callee, ErrorKind> {
+        match instruction {
+            Instruction::Invoke { index, .. } => {
+                self.functions.get(*index as usize).cloned().ok_or_else(|| {
+                    ErrorKind::FunctionNotFound(format!("function #{} of the link table", index))
+                })
+            }
+            Instruction::Call {
+                module, function, ..
+            } => self.resolve(module, function),
+            _ => Err(ErrorKind::InvalidInstruction),
+        }
+    }
+
+    // Function to call for a function value
+    fn closure(&self, value: &Value) -> Result<Callee, ErrorKind> {
+        let Value::Object(handle) = value else {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "function",
+                found: value.type_name(),
+            });
+        };
+
+        match self.heap.get(*handle) {
+            Some(Object::Function(closure)) => Ok(closure.callee.clone()),
+            Some(object) => Err(ErrorKind::TypeMismatch {
+                expected: "function",
+                found: object.type_name(),
+            }),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    fn invoke(
+        &mut self,
+        callee: Callee,
+        args: Vec<Value>,
+        closure: Option<Value>,
+    ) -> Result<(), RuntimeError> {
+        match callee {
+            // The library is held until the call returns
+            Callee::Native(_library, function) => {
+                let result = call_native(function, args).map_err(|kind| self.error(kind, None))?;
+
+                if let Some(result) = result {
+                    self.stack.push(result);
                 }
-                Instruction::Add => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Integer(a + b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Float(a + b));
-                        }
-                        (Value::String(a), Value::String(b)) => {
-                            self.stack.push(Value::String(format!("{}{}", a, b)));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
-                    }
+                Ok(())
+            }
+            Callee::Host(function) => {
+                let base = self.native_args.len();
+                self.native_args.extend(args.iter().cloned());
+
+                let result = function(self, args);
+
+                self.native_args.truncate(base);
+
+                if let Some(result) = result.map_err(|kind| self.error(kind, None))? {
+                    self.stack.push(result);
                 }
-                Instruction::Sub => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
+
+                Ok(())
+            }
+            Callee::Script(function) => {
+                self.local_vars.push(args);
+                self.frames.push(CallFrame {
+                    function: function.clone(),
+                    instruction: 0,
+                    line: None,
+                    closure,
+                });
+
+                self.run(&function.code)?;
+
+                self.frames.pop();
+                self.local_vars.pop();
+
+                Ok(())
+            }
+        }
+    }
+
+    // Run lowered code until it returns or runs past its last instruction. Errors raised
+    // inside a (try) block, in this code or in the functions it calls, continue at its handler
+    fn run(&mut self, code: &Code) -> Result<(), RuntimeError> {
+        let mut pc = 0;
+        let mut handlers = vec![];
+
+        loop {
+            let error = match self.run_from(code, &mut pc, &mut handlers) {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+
+            let Some(handler) = handlers.pop() else {
+                return Err(error);
+            };
+
+            self.catch(&handler, error);
+            pc = handler.target;
+        }
+    }
+
+    // Unwind to the depths the (try) block started at and push the value its handler
+    // receives, the thrown value or the message of a runtime fault
+    fn catch(&mut self, handler: &Handler, error: RuntimeError) {
+        self.stack.truncate(handler.stack);
+        self.local_vars.truncate(handler.locals);
+        self.frames.truncate(handler.frames);
+
+        let value = match error.kind {
+            ErrorKind::Exception(_) => self.exception.take().unwrap_or(Value::Null),
+            kind => Value::String(kind.to_string()),
+        };
+
+        self.stack.push(value);
+    }
+
+    #[inline(always)]
+    fn run_from(
+        &mut self,
+        code: &Code,
+        pc: &mut usize,
+        handlers: &mut Vec<Handler>,
+    ) -> Result<(), RuntimeError> {
+        while let Some(instruction) = code.get(*pc) {
+            let index = *pc;
+            *pc += 1;
+
+            match instruction {
+                Instruction::Call { param_count, .. } | Instruction::Invoke { param_count, .. } => {
+                    let Some(start) = self.stack.len().checked_sub(*param_count as usize) else {
+                        return Err(self.fault(ErrorKind::StackUnderflow, index, instruction));
                     };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Integer(b - a));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Float(b - a));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                    let callee = self
+                        .call_target(instruction)
+                        .map_err(|kind| self.fault(kind, index, instruction))?;
+                    let args = self.stack.split_off(start);
+
+                    // Where the caller is, for the trace of errors raised in the callee
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.instruction = index;
                     }
+
+                    self.invoke(callee, args, None)?;
                 }
-                Instruction::Mul => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
+                Instruction::CallIndirect { param_count } => {
+                    let Some(start) = self.stack.len().checked_sub(*param_count as usize + 1)
+                    else {
+                        return Err(self.fault(ErrorKind::StackUnderflow, index, instruction));
                     };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Integer(a * b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Float(a * b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                    let callee = self
+                        .closure(&self.stack[start])
+                        .map_err(|kind| self.fault(kind, index, instruction))?;
+                    let args = self.stack.split_off(start + 1);
+                    let closure = self.stack.pop();
+
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.instruction = index;
                     }
+
+                    self.invoke(callee, args, closure)?;
                 }
-                Instruction::Div => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
+                Instruction::Return => return Ok(()),
+                Instruction::Jump { target } => *pc = *target as usize,
+                Instruction::JumpIfFalse { target } => {
+                    let value = self
+                        .pop()
+                        .map_err(|kind| self.fault(kind, index, instruction))?;
+
+                    let Value::Boolean(value) = value else {
+                        let kind = ErrorKind::TypeMismatch {
+                            expected: "bool",
+                            found: value.type_name(),
+                        };
+
+                        return Err(self.fault(kind, index, instruction));
                     };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Integer(a / b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Float(a / b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                    if !value {
+                        *pc = *target as usize;
                     }
                 }
-                Instruction::Inc => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+                Instruction::TryStart { handler } => handlers.push(Handler {
+                    target: *handler as usize,
+                    stack: self.stack.len(),
+                    locals: self.local_vars.len(),
+                    frames: self.frames.len(),
+                }),
+                Instruction::TryEnd => {
+                    handlers.pop();
+                }
+                _ => self
+                    .step(instruction)
+                    .map_err(|kind| self.fault(kind, index, instruction))?,
+            }
+        }
 
-                    match a {
-                        Value::Integer(a) => {
-                            self.stack.push(Value::Integer(a + 1));
-                        }
-                        Value::Float(a) => {
-                            self.stack.push(Value::Float(a + 1.0));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
-                    }
+        Ok(())
+    }
+
+    // Free the objects no longer reachable from the stack, the locals of a running
+    // function, a running closure, a global or a thrown value, returns the number of objects freed.
+    // Objects only referenced by the host, e.g. the result of a previous call, are
+    // freed too
+    pub fn collect_garbage(&mut self) -> usize {
+        let roots = self
+            .stack
+            .iter()
+            .chain(self.local_vars.iter().flatten())
+            .chain(self.native_args.iter())
+            .chain(self.exception.iter())
+            .chain(
+                self.frames
+                    .iter()
+                    .filter_map(|frame| frame.closure.as_ref()),
+            )
+            .chain(
+                self.modules
+                    .values()
+                    .flat_map(|module| module.globals.values()),
+            );
+
+        self.heap.collect(roots)
+    }
+
+    // Store an object in the heap, collecting garbage first if it is due. Objects only
+    // referenced by the host are not roots, a host function allocating more than one
+    // object keeps the earlier ones alive by pushing them on the stack
+    pub fn allocate(&mut self, object: Object) -> Result<Value, ErrorKind> {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+
+        let handle = self
+            .heap
+            .allocate(object)
+            .ok_or(ErrorKind::HeapLimit(self.heap.limit.unwrap_or_default()))?;
+
+        Ok(Value::Object(handle))
+    }
+
+    // Fields of the object `value` refers to
+    fn fields(&mut self, value: &Value) -> Result<&mut Vec<Value>, ErrorKind> {
+        let Value::Object(handle) = value else {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "object",
+                found: value.type_name(),
+            });
+        };
+
+        match self.heap.get_mut(*handle) {
+            Some(Object::Values(fields)) => Ok(fields),
+            Some(object) => Err(ErrorKind::TypeMismatch {
+                expected: "object",
+                found: object.type_name(),
+            }),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    // Elements of the list `value` refers to
+    fn list(&mut self, value: &Value) -> Result<&mut Vec<Value>, ErrorKind> {
+        let Value::Object(handle) = value else {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "list",
+                found: value.type_name(),
+            });
+        };
+
+        match self.heap.get_mut(*handle) {
+            Some(Object::List(values)) => Ok(values),
+            Some(object) => Err(ErrorKind::TypeMismatch {
+                expected: "list",
+                found: object.type_name(),
+            }),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    // Entries of the map `value` refers to
+    fn map(&mut self, value: &Value) -> Result<&mut BTreeMap<MapKey, (Value, Value)>, ErrorKind> {
+        let Value::Object(handle) = value else {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "map",
+                found: value.type_name(),
+            });
+        };
+
+        match self.heap.get_mut(*handle) {
+            Some(Object::Map(entries)) => Ok(entries),
+            Some(object) => Err(ErrorKind::TypeMismatch {
+                expected: "map",
+                found: object.type_name(),
+            }),
+            None => Err(ErrorKind::ObjectFreed),
+        }
+    }
+
+    // Pop a map key, returns the key value along with it
+    fn pop_key(&mut self) -> Result<(MapKey, Value), ErrorKind> {
+        let value = self.pop()?;
+
+        match MapKey::from_value(&value) {
+            Some(key) => Ok((key, value)),
+            None => Err(ErrorKind::InvalidKey(value.type_name())),
+        }
+    }
+
+    // Pop a list index, checked against the length of the list later
+    pub(crate) fn pop_index(&mut self) -> Result<i64, ErrorKind> {
+        match self.pop()? {
+            Value::Integer(index) => Ok(index as i64),
+            Value::Long(index) => Ok(index),
+            index => Err(ErrorKind::TypeMismatch {
+                expected: "integer",
+                found: index.type_name(),
+            }),
+        }
+    }
+
+    pub fn get_global(&self, module: &str, name: &str) -> Result<Value, ErrorKind> {
+        let module = self
+            .modules
+            .get(module)
+            .ok_or_else(|| ErrorKind::ModuleNotFound(module.to_string()))?;
+
+        module
+            .globals
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ErrorKind::GlobalNotFound(format!("{}.{}", module.name, name)))
+    }
+
+    // Only globals the module declares can be set
+    pub fn set_global(&mut self, module: &str, name: &str, value: Value) -> Result<(), ErrorKind> {
+        let module = self
+            .modules
+            .get_mut(module)
+            .ok_or_else(|| ErrorKind::ModuleNotFound(module.to_string()))?;
+
+        let Some(global) = module.globals.get_mut(name) else {
+            return Err(ErrorKind::GlobalNotFound(format!(
+                "{}.{}",
+                module.name, name
+            )));
+        };
+
+        *global = value;
+
+        Ok(())
+    }
+
+    // Global of the module of the running function
+    fn global(&mut self, name: &str) -> Result<&mut Value, ErrorKind> {
+        let module = match self.frames.last() {
+            Some(frame) => frame.function.module.as_str(),
+            None => "",
+        };
+
+        self.modules
+            .get_mut(module)
+            .and_then(|module| module.globals.get_mut(name))
+            .ok_or_else(|| ErrorKind::GlobalNotFound(format!("{}.{}", module, name)))
+    }
+
+    // Functions currently being executed, outermost first
+    pub fn trace(&self) -> Vec<Frame> {
+        self.frames.iter().map(CallFrame::to_frame).collect()
+    }
+
+    // Build the error for a failed instruction, recording where it happened
+    fn fault(&mut self, kind: ErrorKind, index: usize, instruction: &Instruction) -> RuntimeError {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.instruction = index;
+        }
+
+        self.error(kind, Some(instruction.bytecode()))
+    }
+
+    // An error raised at the current instruction of the running function
+    fn error(&self, kind: ErrorKind, instruction: Option<ByteCode>) -> RuntimeError {
+        let mut error = RuntimeError::new(kind, instruction);
+
+        if let Some(frame) = self.frames.last() {
+            error.module = frame.function.module.clone();
+            error.function = frame.function.name.clone();
+            error.trace = self.trace();
+        }
+
+        error
+    }
+
+    pub(crate) fn pop(&mut self) -> Result<Value, ErrorKind> {
+        self.stack.pop().ok_or(ErrorKind::StackUnderflow)
+    }
+
+    // Pop the operands of a binary operation, the right hand side is on top of the stack.
+    // Numbers of the same kind and different widths are widened to the wider one, integers
+    // and floats are never mixed implicitly
+    fn pop_operands(&mut self) -> Result<(Value, Value), ErrorKind> {
+        let rhs = self.pop()?;
+        let lhs = self.pop()?;
+
+        Ok(match (lhs, rhs) {
+            (Value::Integer(a), Value::Long(b)) => (Value::Long(a as i64), Value::Long(b)),
+            (Value::Long(a), Value::Integer(b)) => (Value::Long(a), Value::Long(b as i64)),
+            (Value::Float(a), Value::Double(b)) => (Value::Double(a as f64), Value::Double(b)),
+            (Value::Double(a), Value::Float(b)) => (Value::Double(a), Value::Double(b as f64)),
+            operands => operands,
+        })
+    }
+
+    // Convert the number on top of the stack. Narrowing an integer keeps its low bits,
+    // floats are converted to integers rounding toward zero and saturating, NaN becomes 0
+    fn convert(&mut self, instruction: &Instruction) -> Result<(), ErrorKind> {
+        let value = self.pop()?;
+
+        let value = match (instruction, value) {
+            (Instruction::ConvertInteger, Value::Integer(a)) => Value::Integer(a),
+            (Instruction::ConvertInteger, Value::Long(a)) => Value::Integer(a as i32),
+            (Instruction::ConvertInteger, Value::Float(a)) => Value::Integer(a as i32),
+            (Instruction::ConvertInteger, Value::Double(a)) => Value::Integer(a as i32),
+            (Instruction::ConvertLong, Value::Integer(a)) => Value::Long(a as i64),
+            (Instruction::ConvertLong, Value::Long(a)) => Value::Long(a),
+            (Instruction::ConvertLong, Value::Float(a)) => Value::Long(a as i64),
+            (Instruction::ConvertLong, Value::Double(a)) => Value::Long(a as i64),
+            (Instruction::ConvertFloat, Value::Integer(a)) => Value::Float(a as f32),
+            (Instruction::ConvertFloat, Value::Long(a)) => Value::Float(a as f32),
+            (Instruction::ConvertFloat, Value::Float(a)) => Value::Float(a),
+            (Instruction::ConvertFloat, Value::Double(a)) => Value::Float(a as f32),
+            (Instruction::ConvertDouble, Value::Integer(a)) => Value::Double(a as f64),
+            (Instruction::ConvertDouble, Value::Long(a)) => Value::Double(a as f64),
+            (Instruction::ConvertDouble, Value::Float(a)) => Value::Double(a as f64),
+            (Instruction::ConvertDouble, Value::Double(a)) => Value::Double(a),
+            (_, value) => {
+                return Err(ErrorKind::TypeMismatch {
+                    expected: "number",
+                    found: value.type_name(),
+                })
+            }
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn locals(&mut self) -> Result<&mut Vec<Value>, ErrorKind> {
+        self.local_vars
+            .last_mut()
+            .ok_or(ErrorKind::LocalNotFound(0))
+    }
+
+    // Execute an instruction that doesn't change the control flow. Inlined so the
+    // interpreter loop dispatches each instruction without a call
+    #[inline(always)]
+    fn step(&mut self, instruction: &Instruction) -> Result<(), ErrorKind> {
+        match instruction {
+            Instruction::None => {}
+            Instruction::Version {
+                major: _,
+                minor: _,
+                patch: _,
+            } => {}
+            Instruction::Dump => {
+                let heap = &self.heap;
+                let stack: Vec<_> = self.stack.iter().map(|value| heap.debug(value)).collect();
+                let locals: Vec<Vec<_>> = self
+                    .local_vars
+                    .iter()
+                    .map(|locals| locals.iter().map(|value| heap.debug(value)).collect())
+                    .collect();
+
+                println!("Stack: {:?}", stack);
+                println!("Locals: {:?}", locals);
+            }
+            Instruction::Hi => {
+                println!("Hi!");
+            }
+            Instruction::Line { line } => {
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.line = Some(*line);
                 }
-                Instruction::Dec => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+            }
+            Instruction::Params { count } => {
+                let found = self.locals()?.len() as u32;
 
-                    match a {
-                        Value::Integer(a) => {
-                            self.stack.push(Value::Integer(a - 1));
-                        }
-                        Value::Float(a) => {
-                            self.stack.push(Value::Float(a - 1.0));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
-                    }
+                if found != *count {
+                    return Err(ErrorKind::ArityMismatch {
+                        expected: *count,
+                        found,
+                    });
                 }
-                Instruction::Eq => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+            }
+            Instruction::PushConstString { value } => {
+                self.stack.push(Value::String(value.clone()));
+            }
+            Instruction::PushConstInteger { value } => {
+                self.stack.push(Value::Integer(*value));
+            }
+            Instruction::PushConstFloat { value } => {
+                self.stack.push(Value::Float(*value));
+            }
+            Instruction::PushConstLong { value } => {
+                self.stack.push(Value::Long(*value));
+            }
+            Instruction::PushConstDouble { value } => {
+                self.stack.push(Value::Double(*value));
+            }
+            Instruction::PushConstBoolean { value } => {
+                self.stack.push(Value::Boolean(*value));
+            }
+            Instruction::GetLocal { index } => {
+                let value = self
+                    .locals()?
+                    .get(*index as usize)
+                    .cloned()
+                    .ok_or(ErrorKind::LocalNotFound(*index))?;
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a == b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a == b));
-                        }
-                        (Value::String(a), Value::String(b)) => {
-                            self.stack.push(Value::Boolean(a == b));
-                        }
-                        (Value::Boolean(a), Value::Boolean(b)) => {
-                            self.stack.push(Value::Boolean(a == b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
-                    }
+                self.stack.push(value);
+            }
+            Instruction::SetLocal { index } => {
+                let value = self.pop()?;
+
+                let Some(local) = self.locals()?.get_mut(*index as usize) else {
+                    return Err(ErrorKind::LocalNotFound(*index));
+                };
+
+                *local = value;
+            }
+            Instruction::ReserveLocal { size } => {
+                self.locals()?.resize(*size as usize, Value::Null);
+            }
+            Instruction::Allocate { fields } => {
+                let object = self.allocate(Object::Values(vec![Value::Null; *fields as usize]))?;
+                self.stack.push(object);
+            }
+            Instruction::GetField { index } => {
+                let object = self.pop()?;
+
+                let value = if self.is_native(&object) {
+                    self.get_native_field(&object, *index)?
+                } else {
+                    self.fields(&object)?
+                        .get(*index as usize)
+                        .cloned()
+                        .ok_or(ErrorKind::FieldNotFound(*index))?
+                };
+
+                self.stack.push(value);
+            }
+            Instruction::SetField { index } => {
+                let value = self.pop()?;
+                let object = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+
+                if self.is_native(&object) {
+                    return self.set_native_field(&object, *index, value);
                 }
-                Instruction::Ne => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a != b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a != b));
-                        }
-                        (Value::String(a), Value::String(b)) => {
-                            self.stack.push(Value::Boolean(a != b));
-                        }
-                        (Value::Boolean(a), Value::Boolean(b)) => {
-                            self.stack.push(Value::Boolean(a != b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
-                    }
+                let Some(field) = self.fields(&object)?.get_mut(*index as usize) else {
+                    return Err(ErrorKind::FieldNotFound(*index));
+                };
+
+                *field = value;
+            }
+            Instruction::ListNew => {
+                let list = self.allocate(Object::List(vec![]))?;
+                self.stack.push(list);
+            }
+            Instruction::ListPush => {
+                let value = self.pop()?;
+                let list = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+
+                self.list(&list)?.push(value);
+            }
+            Instruction::ListPop => {
+                let list = self.pop()?;
+                let value = self.list(&list)?.pop().ok_or(ErrorKind::EmptyList)?;
+
+                self.stack.push(value);
+            }
+            Instruction::ListLen => {
+                let list = self.pop()?;
+                let len = self.list(&list)?.len();
+
+                self.stack.push(Value::Integer(len as i32));
+            }
+            Instruction::ListGet => {
+                let index = self.pop_index()?;
+                let list = self.pop()?;
+                let values = self.list(&list)?;
+
+                let value = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| values.get(i))
+                    .cloned()
+                    .ok_or(ErrorKind::IndexOutOfBounds {
+                        index,
+                        len: values.len(),
+                    })?;
+
+                self.stack.push(value);
+            }
+            Instruction::ListSet => {
+                let value = self.pop()?;
+                let index = self.pop_index()?;
+                let list = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+                let values = self.list(&list)?;
+                let len = values.len();
+
+                let Some(element) = usize::try_from(index).ok().and_then(|i| values.get_mut(i))
+                else {
+                    return Err(ErrorKind::IndexOutOfBounds { index, len });
+                };
+
+                *element = value;
+            }
+            Instruction::MapNew => {
+                let map = self.allocate(Object::Map(BTreeMap::new()))?;
+                self.stack.push(map);
+            }
+            Instruction::MapInsert => {
+                let value = self.pop()?;
+                let (key, key_value) = self.pop_key()?;
+                let map = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+
+                self.map(&map)?
+                    .entry(key)
+                    .and_modify(|(_, entry)| *entry = value.clone())
+                    .or_insert((key_value, value));
+            }
+            Instruction::MapGet | Instruction::MapRemove => {
+                let (key, key_value) = self.pop_key()?;
+                let map = self.pop()?;
+                let entries = self.map(&map)?;
+
+                let entry = match instruction {
+                    Instruction::MapGet => entries.get(&key).cloned(),
+                    _ => entries.remove(&key),
+                };
+
+                let Some((_, value)) = entry else {
+                    return Err(ErrorKind::KeyNotFound(format!("{:?}", key_value)));
+                };
+
+                self.stack.push(value);
+            }
+            Instruction::MapContains => {
+                let (key, _) = self.pop_key()?;
+                let map = self.pop()?;
+                let contains = self.map(&map)?.contains_key(&key);
+
+                self.stack.push(Value::Boolean(contains));
+            }
+            Instruction::MapLen => {
+                let map = self.pop()?;
+                let len = self.map(&map)?.len();
+
+                self.stack.push(Value::Integer(len as i32));
+            }
+            Instruction::MapKeys => {
+                // The map stays on the stack until the list is allocated, a collection
+                // could free it otherwise
+                let map = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ErrorKind::StackUnderflow)?;
+                let keys = self
+                    .map(&map)?
+                    .values()
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                let list = self.allocate(Object::List(keys))?;
+
+                self.stack.pop();
+                self.stack.push(list);
+            }
+            Instruction::StringLength
+            | Instruction::StringSubstring
+            | Instruction::StringIndexOf
+            | Instruction::StringSplit
+            | Instruction::StringTrim
+            | Instruction::StringUpper
+            | Instruction::StringLower
+            | Instruction::StringCharCode
+            | Instruction::StringFromCharCode
+            | Instruction::StringRepeat => self.string_op(instruction)?,
+            Instruction::ToString
+            | Instruction::ToInteger
+            | Instruction::ToFloat
+            | Instruction::TypeOf
+            | Instruction::IsNull => self.conversion(instruction)?,
+            Instruction::GetProperty { .. }
+            | Instruction::SetProperty { .. }
+            | Instruction::CallMethod { .. } => self.native_op(instruction)?,
+            Instruction::FunctionRef {
+                module,
+                function,
+                captures,
+            } => {
+                let callee = self.resolve(module, function)?;
+
+                let Some(start) = self.stack.len().checked_sub(*captures as usize) else {
+                    return Err(ErrorKind::StackUnderflow);
+                };
+
+                // The captures stay on the stack until the closure is allocated, a
+                // collection could free them otherwise
+                let closure = Closure {
+                    module: module.clone(),
+                    name: function.clone(),
+                    upvalues: vec![],
+                    callee,
+                };
+
+                let value = self.allocate(Object::Function(closure))?;
+                let upvalues = self.stack.split_off(start);
+
+                if let Some(Object::Function(closure)) =
+                    value.handle().and_then(|handle| self.heap.get_mut(handle))
+                {
+                    closure.upvalues = upvalues;
                 }
-                Instruction::Lt => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a < b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a < b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                self.stack.push(value);
+            }
+            Instruction::GetUpvalue { index } => {
+                let closure = self.frames.last().and_then(|frame| frame.closure.as_ref());
+
+                let value = match closure.and_then(|closure| self.heap.get(closure.handle()?)) {
+                    Some(Object::Function(closure)) => closure.upvalues.get(*index as usize),
+                    _ => None,
+                };
+
+                let value = value.cloned().ok_or(ErrorKind::UpvalueNotFound(*index))?;
+                self.stack.push(value);
+            }
+            Instruction::GetGlobal { name } => {
+                let value = self.global(name)?.clone();
+                self.stack.push(value);
+            }
+            Instruction::SetGlobal { name } => {
+                let value = self.pop()?;
+                *self.global(name)? = value;
+            }
+            Instruction::Pop => {
+                self.pop()?;
+            }
+            Instruction::Dup => {
+                let value = self.stack.last().ok_or(ErrorKind::StackUnderflow)?;
+                self.stack.push(value.clone());
+            }
+            Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div => {
+                let op = match instruction {
+                    Instruction::Add => Op::Add,
+                    Instruction::Sub => Op::Sub,
+                    Instruction::Mul => Op::Mul,
+                    _ => Op::Div,
+                };
+
+                let overflow = self.overflow;
+
+                let value = match self.pop_operands()? {
+                    (Value::Integer(a), Value::Integer(b)) => {
+                        Value::Integer(overflow.apply(a, b, op)?)
                     }
-                }
-                Instruction::Le => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+                    (Value::Long(a), Value::Long(b)) => Value::Long(overflow.apply(a, b, op)?),
+                    (Value::Float(a), Value::Float(b)) => Value::Float(op.float(a, b)),
+                    (Value::Double(a), Value::Double(b)) => Value::Double(op.float(a, b)),
+                    (Value::String(a), Value::String(b)) if matches!(op, Op::Add) => {
+                        Value::String(format!("{}{}", a, b))
+                    }
+                    (a, b) => return Err(invalid_operands(&a, &b)),
+                };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a <= b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a <= b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                self.stack.push(value);
+            }
+            Instruction::Rem | Instruction::Mod => {
+                let overflow = self.overflow;
+                let modulo = matches!(instruction, Instruction::Mod);
+
+                let value = match self.pop_operands()? {
+                    (Value::Integer(a), Value::Integer(b)) if modulo => {
+                        Value::Integer(overflow.modulo(a, b)?)
                     }
-                }
-                Instruction::Gt => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+                    (Value::Integer(a), Value::Integer(b)) => Value::Integer(overflow.rem(a, b)?),
+                    (Value::Long(a), Value::Long(b)) if modulo => {
+                        Value::Long(overflow.modulo(a, b)?)
+                    }
+                    (Value::Long(a), Value::Long(b)) => Value::Long(overflow.rem(a, b)?),
+                    (Value::Float(a), Value::Float(b)) if modulo => {
+                        Value::Float(float_modulo(a, b))
+                    }
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a % b),
+                    (Value::Double(a), Value::Double(b)) if modulo => {
+                        Value::Double(float_modulo(a, b))
+                    }
+                    (Value::Double(a), Value::Double(b)) => Value::Double(a % b),
+                    (a, b) => return Err(invalid_operands(&a, &b)),
+                };
+
+                self.stack.push(value);
+            }
+            Instruction::Neg | Instruction::Inc | Instruction::Dec => {
+                let overflow = self.overflow;
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a > b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a > b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                let value = match (instruction, self.pop()?) {
+                    (Instruction::Neg, Value::Integer(a)) => Value::Integer(overflow.neg(a)?),
+                    (Instruction::Neg, Value::Long(a)) => Value::Long(overflow.neg(a)?),
+                    (Instruction::Neg, Value::Float(a)) => Value::Float(-a),
+                    (Instruction::Neg, Value::Double(a)) => Value::Double(-a),
+                    (Instruction::Inc, Value::Integer(a)) => {
+                        Value::Integer(overflow.apply(a, 1, Op::Add)?)
                     }
-                }
-                Instruction::Ge => {
-                    let Some(a) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
-                    let Some(b) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+                    (Instruction::Inc, Value::Long(a)) => {
+                        Value::Long(overflow.apply(a, 1, Op::Add)?)
+                    }
+                    (Instruction::Inc, Value::Float(a)) => Value::Float(a + 1.0),
+                    (Instruction::Inc, Value::Double(a)) => Value::Double(a + 1.0),
+                    (_, Value::Integer(a)) => Value::Integer(overflow.apply(a, 1, Op::Sub)?),
+                    (_, Value::Long(a)) => Value::Long(overflow.apply(a, 1, Op::Sub)?),
+                    (_, Value::Float(a)) => Value::Float(a - 1.0),
+                    (_, Value::Double(a)) => Value::Double(a - 1.0),
+                    (_, a) => {
+                        return Err(ErrorKind::TypeMismatch {
+                            expected: "number",
+                            found: a.type_name(),
+                        })
+                    }
+                };
 
-                    match (a, b) {
-                        (Value::Integer(a), Value::Integer(b)) => {
-                            self.stack.push(Value::Boolean(a >= b));
-                        }
-                        (Value::Float(a), Value::Float(b)) => {
-                            self.stack.push(Value::Boolean(a >= b));
-                        }
-                        _ => {
-                            panic!("Invalid types");
-                        }
+                self.stack.push(value);
+            }
+            Instruction::And | Instruction::Or | Instruction::Xor => {
+                let value = match (instruction, self.pop_operands()?) {
+                    (Instruction::And, (Value::Integer(a), Value::Integer(b))) => {
+                        Value::Integer(a & b)
                     }
-                }
-                Instruction::Module { name: _, code: _ } => {
-                    panic!("Module call not allowed here");
-                }
-                Instruction::LoadModule { name: _, code: _ } => {
-                    panic!("LoadModule call not allowed here");
-                }
-                Instruction::GetFunction { name: _, alias: _ } => {
-                    panic!("GetFunction call not allowed here");
-                }
-                Instruction::Return => {
-                    self.call_return = true;
-                    return;
-                }
-                Instruction::Then {
-                    then_block,
-                    else_block,
-                } => {
-                    let Some(value) = self.stack.pop() else {
-                        panic!("No elements in the stack");
-                    };
+                    (Instruction::And, (Value::Long(a), Value::Long(b))) => Value::Long(a & b),
+                    (Instruction::And, (Value::Boolean(a), Value::Boolean(b))) => {
+                        Value::Boolean(a & b)
+                    }
+                    (Instruction::Or, (Value::Integer(a), Value::Integer(b))) => {
+                        Value::Integer(a | b)
+                    }
+                    (Instruction::Or, (Value::Long(a), Value::Long(b))) => Value::Long(a | b),
+                    (Instruction::Or, (Value::Boolean(a), Value::Boolean(b))) => {
+                        Value::Boolean(a | b)
+                    }
+                    (_, (Value::Integer(a), Value::Integer(b))) => Value::Integer(a ^ b),
+                    (_, (Value::Long(a), Value::Long(b))) => Value::Long(a ^ b),
+                    (_, (Value::Boolean(a), Value::Boolean(b))) => Value::Boolean(a ^ b),
+                    (_, (a, b)) => return Err(invalid_operands(&a, &b)),
+                };
 
-                    if let Value::Boolean(value) = value {
-                        let result = if value {
-                            self.execute(then_block)
-                        } else {
-                            self.execute(else_block)
-                        };
+                self.stack.push(value);
+            }
+            Instruction::Shl | Instruction::Shr => {
+                let left = matches!(instruction, Instruction::Shl);
 
-                        if self.call_return || self.call_break || self.call_continue {
-                            return result;
-                        }
-                    } else {
-                        panic!("Invalid value");
+                // The shift count isn't widened with the value, it only has to be an integer
+                let count = match self.pop()? {
+                    Value::Integer(count) => count as u32,
+                    Value::Long(count) => count as u32,
+                    count => {
+                        return Err(ErrorKind::TypeMismatch {
+                            expected: "integer",
+                            found: count.type_name(),
+                        })
                     }
-                }
-                Instruction::Loop { block } => loop {
-                    let result = self.execute(block);
+                };
 
-                    if self.call_break {
-                        break;
+                let value = match self.pop()? {
+                    Value::Integer(a) if left => Value::Integer(a.wrapping_shl(count)),
+                    Value::Integer(a) => Value::Integer(a.wrapping_shr(count)),
+                    Value::Long(a) if left => Value::Long(a.wrapping_shl(count)),
+                    Value::Long(a) => Value::Long(a.wrapping_shr(count)),
+                    a => {
+                        return Err(ErrorKind::TypeMismatch {
+                            expected: "integer",
+                            found: a.type_name(),
+                        })
                     }
+                };
 
-                    if self.call_continue {
-                        continue;
+                self.stack.push(value);
+            }
+            Instruction::Not => {
+                let value = match self.pop()? {
+                    Value::Integer(a) => Value::Integer(!a),
+                    Value::Long(a) => Value::Long(!a),
+                    a => {
+                        return Err(ErrorKind::TypeMismatch {
+                            expected: "integer",
+                            found: a.type_name(),
+                        })
                     }
+                };
 
-                    if self.call_return {
-                        return result;
+                self.stack.push(value);
+            }
+            Instruction::BoolNot => {
+                let value = match self.pop()? {
+                    Value::Boolean(a) => Value::Boolean(!a),
+                    a => {
+                        return Err(ErrorKind::TypeMismatch {
+                            expected: "bool",
+                            found: a.type_name(),
+                        })
                     }
-                },
-                Instruction::Break => {
-                    self.call_break = true;
-                    return;
-                }
-                Instruction::Continue => {
-                    self.call_continue = true;
-                    return;
-                }
+                };
+
+                self.stack.push(value);
             }
-        }
-    }
+            Instruction::ConvertInteger
+            | Instruction::ConvertLong
+            | Instruction::ConvertFloat
+            | Instruction::ConvertDouble => self.convert(instruction)?,
+            Instruction::Eq | Instruction::Ne => {
+                let equal = match self.pop_operands()? {
+                    (Value::Integer(a), Value::Integer(b)) => a == b,
+                    (Value::Float(a), Value::Float(b)) => a == b,
+                    (Value::Long(a), Value::Long(b)) => a == b,
+                    (Value::Double(a), Value::Double(b)) => a == b,
+                    (Value::String(a), Value::String(b)) => a == b,
+                    (Value::Boolean(a), Value::Boolean(b)) => a == b,
+                    (Value::Object(a), Value::Object(b)) => a == b,
+                    (Value::Null, Value::Null) => true,
+                    // Integers and floats are never mixed implicitly, 1 isn't equal to 1.0
+                    _ => false,
+                };
 
-    pub fn call(&mut self, module: &str, name: &str, args: Vec<Value>) {
-        let Some(module) = self.modules.get_mut(module) else {
-            let Some(dymodule) = self.dymodules.get(module) else {
-                panic!("Module \"{}\" not found", module);
-            };
+                let value = match instruction {
+                    Instruction::Eq => equal,
+                    _ => !equal,
+                };
 
-            if let Some(function) = dymodule.fns.get(name) {
-                let result = function(args);
+                self.stack.push(Value::Boolean(value));
+            }
+            Instruction::Lt | Instruction::Le | Instruction::Gt | Instruction::Ge => {
+                let ordering = match self.pop_operands()? {
+                    (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(&b),
+                    (Value::Float(a), Value::Float(b)) => a.partial_cmp(&b),
+                    (Value::Long(a), Value::Long(b)) => a.partial_cmp(&b),
+                    (Value::Double(a), Value::Double(b)) => a.partial_cmp(&b),
+                    (a, b) => return Err(invalid_operands(&a, &b)),
+                };
 
-                if let Some(result) = result {
-                    self.stack.push(result);
-                }
+                // Comparisons involving NaN are always false
+                let value = ordering.is_some_and(|ordering| match instruction {
+                    Instruction::Lt => ordering.is_lt(),
+                    Instruction::Le => ordering.is_le(),
+                    Instruction::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                });
 
-                return;
-            } else {
-                panic!("Function not found");
+                self.stack.push(Value::Boolean(value));
             }
-        };
+            Instruction::Fn { name: _, code: _ }
+            | Instruction::Module { name: _, code: _ }
+            | Instruction::Struct { name: _, code: _ }
+            | Instruction::Global { .. }
+            | Instruction::LoadModule { name: _, code: _ }
+            | Instruction::GetFunction { name: _, alias: _ } => {
+                return Err(ErrorKind::InvalidInstruction);
+            }
+            Instruction::Throw => {
+                let value = self.pop()?;
+                let message = format!("{:?}", self.heap.debug(&value));
 
-        if let Some(function) = module.get_function_mut(name) {
-            self.local_vars.push(args);
-            let code = function.code.clone();
-            self.execute(&code);
-            self.local_vars.pop();
-            return;
-        } else {
-            panic!("Function not found");
-        }
-    }
+                self.exception = Some(value);
 
-    pub fn has_function(&self, module: &str, name: &str) -> bool {
-        if let Some(module) = self.modules.get(module) {
-            if let Some(_) = module.get_function(name) {
-                return true;
-            }
-        } else if let Some(dymodule) = self.dymodules.get(module) {
-            if let Some(_) = dymodule.fns.get(name) {
-                return true;
+                return Err(ErrorKind::Exception(message));
             }
+            // Structured blocks are lowered to jumps before they are run
+            Instruction::Then { .. }
+            | Instruction::Loop { .. }
+            | Instruction::Try { .. }
+            | Instruction::Break
+            | Instruction::Continue => return Err(ErrorKind::InvalidInstruction),
+            // Control flow is handled by `run`
+            Instruction::Call { .. }
+            | Instruction::Invoke { .. }
+            | Instruction::CallIndirect { .. }
+            | Instruction::Return
+            | Instruction::Jump { .. }
+            | Instruction::JumpIfFalse { .. }
+            | Instruction::TryStart { .. }
+            | Instruction::TryEnd => return Err(ErrorKind::InvalidInstruction),
         }
 
-        false
+        Ok(())
+    }
+
+    pub fn has_function(&self, module: &str, name: &str) -> bool {
+        self.resolve(module, name).is_ok()
     }
 
     pub fn get_function(&self, module: &str, name: &str) -> Option<&Function> {
@@ -546,3 +1263,720 @@ impl<'a> VirtualMachine {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        asm::assemble, load_modules, ByteCode, ErrorKind, MapKey, NativeObject, Object, Overflow,
+        Value, VirtualMachine,
+    };
+
+    fn vm(source: &str) -> VirtualMachine {
+        let (modules, _) = load_modules(&assemble(source).unwrap()).unwrap();
+        let mut vm = VirtualMachine::new();
+
+        for module in modules {
+            vm.add_module(module);
+        }
+
+        vm
+    }
+
+    #[test]
+    fn vm_runtime_errors() {
+        let mut vm = vm("(mod main
+            (fn underflow (i32.const 1) (op.add))
+            (fn types (i32.const 1) (f32.const 1.0) (op.add))
+            (fn local (local.get 3))
+            (fn missing (call main nope 0))
+            (fn nested (call main types 0))
+            (fn ok (i32.const 2))
+        )");
+
+        let error = vm.call("main", "underflow", vec![]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::StackUnderflow);
+        assert_eq!(error.function, "underflow");
+        assert_eq!(error.instruction, Some(ByteCode::Add));
+
+        let error = vm.call("main", "types", vec![]).unwrap_err();
+        assert_eq!(
+            error.kind,
+            ErrorKind::InvalidOperands {
+                lhs: "int",
+                rhs: "float"
+            }
+        );
+
+        let error = vm.call("main", "local", vec![]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::LocalNotFound(3));
+
+        let error = vm.call("main", "missing", vec![]).unwrap_err();
+        assert_eq!(error.instruction, Some(ByteCode::Call));
+        assert_eq!(
+            error.kind,
+            ErrorKind::FunctionNotFound("main.nope".to_string())
+        );
+
+        // Errors are attributed to the function that raised them
+        let error = vm.call("main", "nested", vec![]).unwrap_err();
+        assert_eq!(error.function, "types");
+
+        let error = vm.call("other", "main", vec![]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::ModuleNotFound("other".to_string()));
+
+        // The VM is still usable after an error
+        assert!(matches!(
+            vm.call("main", "ok", vec![]),
+            Ok(Some(Value::Integer(2)))
+        ));
+        assert!(vm.stack.is_empty());
+        assert!(vm.local_vars.is_empty());
+        assert!(vm.frames.is_empty());
+    }
+
+    #[test]
+    fn vm_garbage_collection() {
+        // Every iteration makes a two object cycle that is garbage by the next one
+        let mut vm = vm("(mod main
+            (fn cycles (fn.params 1)
+                (loop
+                    (local.get 0) (i32.const 0) (cmp.eq) (then (break))
+                    (alloc 1) (alloc 1) (field.set 0) (dup) (field.get 0) (field.set 0) (pop)
+                    (local.get 0) (op.dec) (local.set 0))
+                (alloc 1) (local.get 0) (field.set 0))
+        )");
+
+        vm.heap.set_threshold(8);
+        let result = vm.call("main", "cycles", vec![Value::Integer(100)]);
+
+        let Ok(Some(Value::Object(handle))) = result else {
+            panic!("Unexpected result: {:?}", result);
+        };
+
+        assert!(vm.heap.stats().collections > 0);
+        assert!(vm.heap.stats().peak <= 10);
+        assert_eq!(
+            format!("{:?}", vm.heap.debug(&Value::Object(handle))),
+            "Object[0]"
+        );
+
+        // Nothing references the result once it is back in the host
+        vm.collect_garbage();
+        assert!(vm.heap.is_empty());
+        assert!(vm.heap.get(handle).is_none());
+
+        // Garbage is collected before the limit is enforced, live objects count against it
+        vm.heap.limit = Some(3);
+        assert!(vm.call("main", "cycles", vec![Value::Integer(10)]).is_ok());
+
+        vm.heap.limit = Some(1);
+        vm.heap.set_threshold(1024);
+        let error = vm
+            .call("main", "cycles", vec![Value::Integer(2)])
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::HeapLimit(1));
+    }
+
+    #[test]
+    fn vm_wide_numbers() {
+        let mut vm = vm("(mod main
+            (fn widen (i32.const 2147483647) (i64.const 1) (op.add))
+            (fn double (f32.const 0.5) (f64.const 0.25) (op.mul))
+            (fn compare (i64.const 3000000000) (i32.const 7) (cmp.gt))
+            (fn mixed (i64.const 1) (f64.const 1.0) (op.add))
+            (fn narrow (i64.const 4294967297) (conv.i32))
+            (fn truncate (f64.const -2.75) (conv.i64))
+            (fn saturate (f64.const 1e300) (conv.i32))
+            (fn to_float (i32.const 3) (conv.f64) (f64.const 0.5) (op.add))
+        )");
+
+        let mut call = |name| vm.call("main", name, vec![]);
+
+        assert!(matches!(call("widen"), Ok(Some(Value::Long(2147483648)))));
+        assert!(matches!(call("double"), Ok(Some(Value::Double(0.125)))));
+        assert!(matches!(call("compare"), Ok(Some(Value::Boolean(true)))));
+        assert_eq!(
+            call("mixed").unwrap_err().kind,
+            ErrorKind::InvalidOperands {
+                lhs: "long",
+                rhs: "double"
+            }
+        );
+        assert!(matches!(call("narrow"), Ok(Some(Value::Integer(1)))));
+        assert!(matches!(call("truncate"), Ok(Some(Value::Long(-2)))));
+        assert!(matches!(
+            call("saturate"),
+            Ok(Some(Value::Integer(i32::MAX)))
+        ));
+        assert!(matches!(call("to_float"), Ok(Some(Value::Double(3.5)))));
+    }
+
+    #[test]
+    fn vm_overflow() {
+        let mut vm = vm("(mod main
+            (fn add (i32.const 2147483647) (i32.const 1) (op.add))
+            (fn neg (i64.const -9223372036854775808) (op.neg))
+            (fn div (i32.const 1) (i32.const 0) (op.div))
+            (fn mod (i32.const -7) (i32.const 3) (op.mod))
+            (fn float (f32.const 1.0) (f32.const 0.0) (op.div))
+        )");
+
+        let error = vm.call("main", "add", vec![]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::IntegerOverflow);
+        assert_eq!(error.instruction, Some(ByteCode::Add));
+
+        let error = vm.call("main", "div", vec![]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::DivisionByZero);
+
+        assert!(matches!(
+            vm.call("main", "mod", vec![]),
+            Ok(Some(Value::Integer(2)))
+        ));
+        assert!(matches!(
+            vm.call("main", "float", vec![]),
+            Ok(Some(Value::Float(f))) if f.is_infinite()
+        ));
+
+        vm.overflow = Overflow::Wrapping;
+        assert!(matches!(
+            vm.call("main", "add", vec![]),
+            Ok(Some(Value::Integer(i32::MIN)))
+        ));
+
+        vm.overflow = Overflow::Saturating;
+        assert!(matches!(
+            vm.call("main", "neg", vec![]),
+            Ok(Some(Value::Long(i64::MAX)))
+        ));
+        assert_eq!(
+            vm.call("main", "div", vec![]).unwrap_err().kind,
+            ErrorKind::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn vm_bitwise() {
+        let mut vm = vm("(mod main
+            (fn bits (i32.const 12) (i32.const 10) (op.and) (i32.const 1) (op.or) (i32.const 3) (op.xor))
+            (fn shifts (i64.const -16) (i32.const 2) (op.shr) (i32.const 33) (op.shl))
+            (fn wrap (i32.const 1) (i32.const 33) (op.shl))
+            (fn not (i32.const 0) (op.not))
+            (fn logic (bool.const true) (bool.const false) (op.or) (bool.not))
+            (fn invalid (bool.const true) (op.not))
+        )");
+
+        let mut call = |name| vm.call("main", name, vec![]);
+
+        assert!(matches!(call("bits"), Ok(Some(Value::Integer(10)))));
+        assert!(matches!(
+            call("shifts"),
+            Ok(Some(Value::Long(-34359738368)))
+        ));
+        assert!(matches!(call("wrap"), Ok(Some(Value::Integer(2)))));
+        assert!(matches!(call("not"), Ok(Some(Value::Integer(-1)))));
+        assert!(matches!(call("logic"), Ok(Some(Value::Boolean(false)))));
+        assert_eq!(
+            call("invalid").unwrap_err().kind,
+            ErrorKind::TypeMismatch {
+                expected: "integer",
+                found: "bool"
+            }
+        );
+    }
+
+    #[test]
+    fn vm_lists() {
+        let mut vm = vm("(mod main
+            (fn build (list.new) (i32.const 1) (list.push) (str.const a) (list.push)
+                (list.new) (list.push) (i64.const 0) (i32.const 5) (list.set))
+            (fn len (local.get 0) (list.len))
+            (fn get (local.get 0) (local.get 1) (list.get))
+            (fn pop (local.get 0) (list.pop))
+            (fn field (local.get 0) (field.get 0))
+        )");
+
+        let list = vm.call("main", "build", vec![]).unwrap().unwrap();
+        assert_eq!(format!("{:?}", vm.heap.debug(&list)), "[5, \"a\", []]");
+
+        let mut call = |name, args| vm.call("main", name, args);
+
+        assert!(matches!(
+            call("len", vec![list.clone()]),
+            Ok(Some(Value::Integer(3)))
+        ));
+        assert!(matches!(
+            call("get", vec![list.clone(), Value::Integer(1)]),
+            Ok(Some(Value::String(s))) if s == "a"
+        ));
+        assert_eq!(
+            call("get", vec![list.clone(), Value::Integer(-1)])
+                .unwrap_err()
+                .kind,
+            ErrorKind::IndexOutOfBounds { index: -1, len: 3 }
+        );
+        assert_eq!(
+            call("field", vec![list.clone()]).unwrap_err().kind,
+            ErrorKind::TypeMismatch {
+                expected: "object",
+                found: "list"
+            }
+        );
+
+        for _ in 0..3 {
+            call("pop", vec![list.clone()]).unwrap();
+        }
+
+        assert_eq!(
+            call("pop", vec![list.clone()]).unwrap_err().kind,
+            ErrorKind::EmptyList
+        );
+    }
+
+    #[test]
+    fn vm_maps() {
+        let mut vm = vm("(mod main
+            (fn build (map.new)
+                (str.const b) (i32.const 1) (map.insert)
+                (i64.const 7) (str.const seven) (map.insert)
+                (str.const a) (list.new) (map.insert)
+                (i32.const 7) (str.const SEVEN) (map.insert))
+            (fn get (local.get 0) (local.get 1) (map.get))
+            (fn remove (local.get 0) (local.get 1) (map.remove))
+            (fn contains (local.get 0) (local.get 1) (map.contains))
+            (fn len (local.get 0) (map.len))
+            (fn keys (local.get 0) (map.keys))
+        )");
+
+        let map = vm.call("main", "build", vec![]).unwrap().unwrap();
+        assert_eq!(
+            format!("{:?}", vm.heap.debug(&map)),
+            "{7: \"SEVEN\", \"a\": [], \"b\": 1}"
+        );
+
+        let keys = vm.call("main", "keys", vec![map.clone()]).unwrap().unwrap();
+        assert_eq!(format!("{:?}", vm.heap.debug(&keys)), "[7, \"a\", \"b\"]");
+
+        let mut call = |name, args| vm.call("main", name, args);
+
+        assert!(matches!(
+            call("get", vec![map.clone(), Value::Long(7)]),
+            Ok(Some(Value::String(s))) if s == "SEVEN"
+        ));
+        assert!(matches!(
+            call("remove", vec![map.clone(), Value::String("b".to_string())]),
+            Ok(Some(Value::Integer(1)))
+        ));
+        assert!(matches!(
+            call(
+                "contains",
+                vec![map.clone(), Value::String("b".to_string())]
+            ),
+            Ok(Some(Value::Boolean(false)))
+        ));
+        assert!(matches!(
+            call("len", vec![map.clone()]),
+            Ok(Some(Value::Integer(2)))
+        ));
+        assert_eq!(
+            call("get", vec![map.clone(), Value::String("b".to_string())])
+                .unwrap_err()
+                .kind,
+            ErrorKind::KeyNotFound("\"b\"".to_string())
+        );
+        assert_eq!(
+            call("get", vec![map.clone(), Value::Float(1.0)])
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidKey("float")
+        );
+    }
+
+    #[test]
+    fn vm_conversions() {
+        let mut vm = vm("(mod main
+            (fn eq (local.get 0) (local.get 1) (cmp.eq))
+            (fn to_string (local.get 0) (to.string))
+            (fn to_int (local.get 0) (to.int))
+            (fn type_of (local.get 0) (type.of))
+            (fn is_null (local.get 0) (is.null))
+            (fn list (list.new) (i32.const 1) (list.push) (str.const a) (list.push))
+        )");
+
+        let list = vm.call("main", "list", vec![]).unwrap().unwrap();
+        let mut call = |name, args| vm.call("main", name, args).unwrap().unwrap();
+
+        assert!(matches!(
+            call("eq", vec![Value::Integer(1), Value::Float(1.0)]),
+            Value::Boolean(false)
+        ));
+        assert!(matches!(
+            call("eq", vec![list.clone(), list.clone()]),
+            Value::Boolean(true)
+        ));
+        assert!(matches!(
+            call("eq", vec![Value::Null, Value::String("null".to_string())]),
+            Value::Boolean(false)
+        ));
+        assert!(matches!(
+            call("to_string", vec![list.clone()]),
+            Value::String(s) if s == "[1, \"a\"]"
+        ));
+        assert!(matches!(
+            call("to_string", vec![Value::Double(0.5)]),
+            Value::String(s) if s == "0.5"
+        ));
+        assert!(matches!(
+            call("to_int", vec![Value::String("12".to_string())]),
+            Value::Integer(12)
+        ));
+        assert!(matches!(
+            call("type_of", vec![list.clone()]),
+            Value::String(s) if s == "list"
+        ));
+        assert!(matches!(
+            call("type_of", vec![Value::Long(1)]),
+            Value::String(s) if s == "long"
+        ));
+        assert!(matches!(
+            call("is_null", vec![Value::Null]),
+            Value::Boolean(true)
+        ));
+
+        let error = vm
+            .call("main", "to_int", vec![Value::String("twelve".to_string())])
+            .unwrap_err();
+        assert_eq!(error.instruction, Some(ByteCode::ToInteger));
+    }
+
+    #[test]
+    fn vm_closures() {
+        let mut vm = vm("(mod main
+            (fn add (fn.params 2) (local.get 0) (local.get 1) (op.add))
+            (fn adder (fn.params 1) (local.get 0) (upvalue.get 0) (op.add))
+            (fn make_adder (fn.params 1) (local.get 0) (fn.ref main adder 1))
+            (fn apply (fn.params 2) (local.get 0) (local.get 1) (call.indirect 1))
+            (fn main (i32.const 10) (call main make_adder 1) (i32.const 5) (call main apply 2))
+            (fn direct (fn.ref main add) (i32.const 1) (i32.const 2) (call.indirect 2))
+            (fn counter (list.new) (fn.ref main count 1))
+            (fn count (upvalue.get 0) (i32.const 1) (list.push) (list.len))
+            (fn not_function (i32.const 1) (call.indirect 0))
+            (fn no_upvalue (upvalue.get 0))
+        )");
+
+        vm.link().unwrap();
+
+        assert!(matches!(
+            vm.call("main", "main", vec![]),
+            Ok(Some(Value::Integer(15)))
+        ));
+        assert!(matches!(
+            vm.call("main", "direct", vec![]),
+            Ok(Some(Value::Integer(3)))
+        ));
+
+        // The captured list is only reachable through the closure
+        let counter = vm.call("main", "counter", vec![]).unwrap().unwrap();
+        vm.stack.push(counter.clone());
+        vm.collect_garbage();
+
+        for expected in 1..=2 {
+            assert!(matches!(
+                vm.call_function(&counter, vec![]),
+                Ok(Some(Value::Integer(count))) if count == expected
+            ));
+        }
+
+        assert_eq!(vm.type_of(&counter), "function");
+        assert_eq!(format!("{:?}", vm.heap.debug(&counter)), "fn main.count");
+
+        // Allocating the closure collects, its captures must survive the collection
+        vm.stack.clear();
+        vm.collect_garbage();
+        vm.heap.set_threshold(1);
+        let counter = vm.call("main", "counter", vec![]).unwrap().unwrap();
+        vm.stack.push(counter.clone());
+
+        assert!(matches!(
+            vm.call_function(&counter, vec![]),
+            Ok(Some(Value::Integer(1)))
+        ));
+
+        assert_eq!(
+            vm.call("main", "not_function", vec![]).unwrap_err().kind,
+            ErrorKind::TypeMismatch {
+                expected: "function",
+                found: "int"
+            }
+        );
+        assert_eq!(
+            vm.call("main", "no_upvalue", vec![]).unwrap_err().kind,
+            ErrorKind::UpvalueNotFound(0)
+        );
+    }
+
+    #[test]
+    fn vm_exceptions() {
+        let mut vm = vm("(mod main
+            (fn fail (str.const boom) (throw))
+            (fn caught (i32.const 1) (try (i32.const 2) (call main fail 0) catch))
+            (fn fault (try (i32.const 1) (i32.const 0) (op.div) catch))
+            (fn nested (try (try (call main fail 0) catch (throw)) catch (str.const \" again\") (op.add)))
+            (fn escape
+                (local.reserve 1) (i32.const 0) (local.set 0)
+                (loop (try (local.get 0) (i32.const 3) (cmp.eq) (then (break))
+                    (local.get 0) (i32.const 1) (op.add) (local.set 0) catch (pop)))
+                (i32.const 0) (throw))
+            (fn after (call main escape 0))
+            (fn uncaught (i32.const 1) (list.new) (throw))
+        )");
+
+        vm.link().unwrap();
+
+        assert!(matches!(
+            vm.call("main", "caught", vec![]),
+            Ok(Some(Value::String(s))) if s == "boom"
+        ));
+        assert_eq!(vm.stack.len(), 0);
+        assert!(matches!(
+            vm.call("main", "fault", vec![]),
+            Ok(Some(Value::String(s))) if s == "Division by zero"
+        ));
+        assert!(matches!(
+            vm.call("main", "nested", vec![]),
+            Ok(Some(Value::String(s))) if s == "boom again"
+        ));
+
+        // (break) leaves the (try) block, the throw after the loop isn't caught by it
+        assert_eq!(
+            vm.call("main", "after", vec![]).unwrap_err().kind,
+            ErrorKind::Exception("0".to_string())
+        );
+        assert_eq!(
+            vm.call("main", "uncaught", vec![]).unwrap_err().kind,
+            ErrorKind::Exception("[]".to_string())
+        );
+    }
+
+    #[test]
+    fn vm_register_native() {
+        let mut vm = vm("(mod main
+            (fn add (fn.params 2) (local.get 0) (local.get 1) (op.add))
+            (fn main (str.const hi) (call host log 1)
+                (fn.ref main add) (i32.const 2) (i32.const 3) (call host apply 3))
+            (fn pair (i32.const 1) (list.new) (call host pair 2))
+            (fn fail (try (call host fail 0) catch))
+            (fn throws (list.new) (throw))
+            (fn rethrown (try (fn.ref main throws) (call host apply 1) catch))
+            (fn shadowed (i32.const 1))
+            (fn local (call main twice 0) (call main shadowed 0) (op.add))
+        )");
+
+        let log = Rc::new(RefCell::new(vec![]));
+        let logged = log.clone();
+
+        vm.register_native("host", "log", move |_, args| {
+            logged.borrow_mut().push(format!("{:?}", args));
+            Ok(None)
+        });
+        vm.register_native("host", "apply", |vm, args| {
+            vm.call_function(&args[0], args[1..].to_vec())
+                .map_err(|error| error.kind)
+        });
+        vm.register_native("host", "pair", |vm, args| {
+            // The list argument is kept alive while the pair is allocated
+            vm.collect_garbage();
+            vm.allocate(Object::List(args)).map(Some)
+        });
+        vm.register_native("host", "fail", |_, _| {
+            Err(ErrorKind::InvalidArgument("no".to_string()))
+        });
+
+        assert!(vm.has_function("host", "log"));
+
+        // Host functions can be added to script modules, and replace their functions
+        vm.register_native("main", "twice", |_, _| Ok(Some(Value::Integer(2))));
+        vm.register_native("main", "shadowed", |_, _| Ok(Some(Value::Integer(10))));
+
+        assert!(vm.has_function("main", "twice"));
+        assert!(!vm.has_function("main", "missing"));
+        assert!(matches!(
+            vm.call("main", "local", vec![]),
+            Ok(Some(Value::Integer(12)))
+        ));
+
+        // Through (call) before linking and (invoke) after
+        for _ in 0..2 {
+            assert!(matches!(
+                vm.call("main", "main", vec![]),
+                Ok(Some(Value::Integer(5)))
+            ));
+            vm.link().unwrap();
+        }
+
+        assert_eq!(*log.borrow(), vec!["[\"hi\"]", "[\"hi\"]"]);
+        assert!(matches!(
+            vm.call("main", "local", vec![]),
+            Ok(Some(Value::Integer(12)))
+        ));
+
+        let pair = vm.call("main", "pair", vec![]).unwrap().unwrap();
+        assert_eq!(format!("{:?}", vm.heap.debug(&pair)), "[1, []]");
+
+        assert!(matches!(
+            vm.call("main", "fail", vec![]),
+            Ok(Some(Value::String(s))) if s == "Invalid argument: no"
+        ));
+
+        // The value thrown by a callback reaches the (try) around the host function
+        let caught = vm.call("main", "rethrown", vec![]).unwrap().unwrap();
+        assert_eq!(vm.type_of(&caught), "list");
+    }
+
+    #[test]
+    fn vm_native_objects() {
+        struct Counter {
+            count: i32,
+            finalized: Rc<RefCell<bool>>,
+        }
+
+        impl NativeObject for Counter {
+            fn type_name(&self) -> &'static str {
+                "Counter"
+            }
+
+            fn get(&self, key: &MapKey) -> Result<Value, ErrorKind> {
+                match key {
+                    MapKey::String(name) if name == "count" => Ok(Value::Integer(self.count)),
+                    MapKey::Integer(0) => Ok(Value::Integer(self.count)),
+                    MapKey::String(name) => Err(ErrorKind::PropertyNotFound(name.clone())),
+                    MapKey::Integer(index) => Err(ErrorKind::FieldNotFound(*index as u32)),
+                }
+            }
+
+            fn set(&mut self, key: &MapKey, value: Value) -> Result<(), ErrorKind> {
+                let Value::Integer(count) = value else {
+                    return Err(ErrorKind::TypeMismatch {
+                        expected: "int",
+                        found: value.type_name(),
+                    });
+                };
+
+                match key {
+                    MapKey::String(name) if name == "count" => self.count = count,
+                    _ => return Err(ErrorKind::PropertyNotFound(format!("{:?}", key))),
+                }
+
+                Ok(())
+            }
+
+            fn call(&mut self, method: &str, args: Vec<Value>) -> Result<Option<Value>, ErrorKind> {
+                match (method, args.as_slice()) {
+                    ("add", [Value::Integer(n)]) => self.count += n,
+                    _ => return Err(ErrorKind::MethodNotFound(method.to_string())),
+                }
+
+                Ok(None)
+            }
+
+            fn finalize(&mut self) {
+                *self.finalized.borrow_mut() = true;
+            }
+        }
+
+        let mut vm = vm("(mod main
+            (fn count (local.get 0) (prop.get count))
+            (fn bump (local.get 0) (i32.const 5) (call.method add 1)
+                (local.get 0) (i32.const 10) (prop.set count) (field.get 0))
+            (fn type (local.get 0) (type.of))
+            (fn missing (local.get 0) (prop.get size))
+            (fn unknown (local.get 0) (call.method reset 0))
+        )");
+
+        let finalized = Rc::new(RefCell::new(false));
+
+        let counter = vm
+            .new_native(Counter {
+                count: 1,
+                finalized: finalized.clone(),
+            })
+            .unwrap();
+
+        let call = |vm: &mut VirtualMachine, name| vm.call("main", name, vec![counter.clone()]);
+
+        assert!(matches!(
+            call(&mut vm, "count"),
+            Ok(Some(Value::Integer(1)))
+        ));
+        assert!(matches!(
+            call(&mut vm, "bump"),
+            Ok(Some(Value::Integer(10)))
+        ));
+        assert!(matches!(call(&mut vm, "type"), Ok(Some(Value::String(s))) if s == "Counter"));
+        assert_eq!(
+            call(&mut vm, "missing").unwrap_err().kind,
+            ErrorKind::PropertyNotFound("size".to_string())
+        );
+        assert_eq!(
+            call(&mut vm, "unknown").unwrap_err().kind,
+            ErrorKind::MethodNotFound("reset".to_string())
+        );
+
+        assert_eq!(format!("{:?}", vm.heap.debug(&counter)), "Counter");
+        assert_eq!(vm.native::<Counter>(&counter).unwrap().count, 10);
+        vm.native_mut::<Counter>(&counter).unwrap().count = 3;
+        assert!(matches!(
+            call(&mut vm, "count"),
+            Ok(Some(Value::Integer(3)))
+        ));
+
+        // Objects without behaviour fail every operation
+        struct Opaque;
+        impl NativeObject for Opaque {}
+
+        let opaque = vm.new_native(Opaque).unwrap();
+
+        assert!(matches!(
+            vm.native::<Counter>(&opaque),
+            Err(ErrorKind::TypeMismatch {
+                found: "native object",
+                ..
+            })
+        ));
+        assert_eq!(
+            vm.call("main", "count", vec![opaque]).unwrap_err().kind,
+            ErrorKind::PropertyNotFound("count".to_string())
+        );
+
+        // The counter isn't rooted, the next collection finalizes it
+        assert!(!*finalized.borrow());
+        vm.collect_garbage();
+        assert!(*finalized.borrow());
+    }
+
+    #[test]
+    fn vm_stack_trace() {
+        let mut vm = vm("(mod main
+            (fn main (line 1) (i32.const 1) (line 2) (call main inner 1))
+            (fn inner (line 7) (bool.const true)
+                (then (line 9) (local.get 0) (bool.const true) (op.add)))
+        )");
+
+        let error = vm.call("main", "main", vec![]).unwrap_err();
+        let trace: Vec<String> = error.trace.iter().map(|f| f.to_string()).collect();
+
+        assert_eq!(
+            trace,
+            vec![
+                "main.main, instruction 3, line 2",
+                "main.inner, instruction 6, line 9",
+            ]
+        );
+        assert_eq!(error.function, "inner");
+        assert_eq!(
+            error.traceback().lines().next(),
+            Some("Traceback (most recent call last):")
+        );
+    }
+}
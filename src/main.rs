@@ -1,8 +1,9 @@
 mod options;
 
-use std::time::Instant;
+use std::{path::Path, time::Instant};
 
-use ms_runtime::{asm::assemble, Instruction};
+use ms_compiler::compile as compile_source;
+use ms_runtime::{asm::assemble, Instruction, Overflow};
 use options::Options;
 
 // run subcommand
@@ -27,6 +28,19 @@ fn run(args: Vec<String>) {
                 println!("Options:");
                 println!("  -entry <function>  Entry point function (default: main.main)");
                 println!("  -time              Print execution time");
+                println!("  -heap-limit <n>    Maximum number of live objects");
+                println!("  -gc-threshold <n>  Live objects before the first collection");
+                println!("  -gc-stats          Print garbage collector statistics");
+                println!(
+                    "  -overflow <mode>   Integer overflow: check (default), wrap or saturate"
+                );
+                println!("  -module-path <dir> Directory to search for native modules, repeatable");
+                println!();
+                println!(
+                    "Native modules are searched in the -module-path directories, then in {}, next",
+                    ms_runtime::MODULE_PATH_VAR
+                );
+                println!("to the script and in the current directory");
                 return;
             }
             "-entry" => {
@@ -40,6 +54,42 @@ fn run(args: Vec<String>) {
             "-time" => {
                 options.time = true;
             }
+            "-heap-limit" => match it.next().map(|limit| limit.parse()) {
+                Some(Ok(limit)) => options.heap_limit = Some(limit),
+                _ => {
+                    println!("Error: Expected a number of objects after -heap-limit");
+                    return;
+                }
+            },
+            "-gc-threshold" => match it.next().map(|threshold| threshold.parse()) {
+                Some(Ok(threshold)) => options.gc_threshold = Some(threshold),
+                _ => {
+                    println!("Error: Expected a number of objects after -gc-threshold");
+                    return;
+                }
+            },
+            "-module-path" => {
+                if let Some(dir) = it.next() {
+                    options.module_paths.push(dir.into());
+                } else {
+                    println!("Error: Missing directory after -module-path");
+                    return;
+                }
+            }
+            "-gc-stats" => {
+                options.gc_stats = true;
+            }
+            "-overflow" => {
+                options.overflow = match it.next().map(String::as_str) {
+                    Some("check") => Overflow::Checked,
+                    Some("wrap") => Overflow::Wrapping,
+                    Some("saturate") => Overflow::Saturating,
+                    _ => {
+                        println!("Error: Expected check, wrap or saturate after -overflow");
+                        return;
+                    }
+                }
+            }
             _ => {
                 if options.input.is_empty() {
                     options.input = arg.to_string();
@@ -63,7 +113,17 @@ fn run(args: Vec<String>) {
     let compile_time = Instant::now();
 
     let code = if options.input.ends_with(".ms") {
-        todo!()
+        let source = std::fs::read_to_string(&options.input).expect("Failed to read file");
+
+        match compile_source(&source) {
+            Ok(code) => code,
+            Err(errors) => {
+                for error in errors.lines() {
+                    println!("Error: {}:{}", options.input, error);
+                }
+                return;
+            }
+        }
     } else if options.input.ends_with(".msa") {
         assemble(&std::fs::read_to_string(&options.input).expect("Failed to read file"))
             .expect("Failed to assemble code")
@@ -77,9 +137,25 @@ fn run(args: Vec<String>) {
     let compile_time = compile_time.elapsed();
     let load_time = Instant::now();
 
-    let mods = ms_runtime::load_modules(&code).expect("Failed to load modules");
+    let search_paths =
+        ms_runtime::search_paths(&options.module_paths, Some(Path::new(&options.input)));
+
+    let mods = match ms_runtime::load_modules_from(&code, &search_paths) {
+        Ok(mods) => mods,
+        Err(error) => {
+            println!("Error: {}", error);
+            return;
+        }
+    };
     let mut vm = ms_runtime::VirtualMachine::new();
 
+    vm.heap.limit = options.heap_limit;
+    vm.overflow = options.overflow;
+
+    if let Some(threshold) = options.gc_threshold {
+        vm.heap.set_threshold(threshold);
+    }
+
     for module in mods.0 {
         vm.add_module(module);
     }
@@ -88,6 +164,13 @@ fn run(args: Vec<String>) {
         vm.add_dynamic_module(module);
     }
 
+    if let Err(errors) = vm.link() {
+        for error in errors.lines() {
+            println!("Error: {}", error);
+        }
+        return;
+    }
+
     // Get the entry point function from the options.entry string (get the last part of the string)
     let parts: Vec<&str> = options.entry.split('.').collect();
 
@@ -107,9 +190,19 @@ fn run(args: Vec<String>) {
     let load_time = load_time.elapsed();
 
     let execute_time = Instant::now();
-    vm.call(&module, function, vec![]);
+    let result = vm.call(&module, function, vec![]);
     let execute_time = execute_time.elapsed();
 
+    if options.gc_stats {
+        println!("{}", vm.heap.stats());
+    }
+
+    if let Err(error) = result {
+        println!("{}", error.traceback());
+        println!("Error: {}", error);
+        return;
+    }
+
     if options.time {
         println!("Compile time: {:?}", compile_time);
         println!("Load time: {:?}", load_time);
@@ -164,7 +257,15 @@ fn compile(args: Vec<String>) {
     let source = std::fs::read_to_string(&options.input).expect("Failed to read file");
 
     let code = if options.input.ends_with(".ms") {
-        todo!()
+        match compile_source(&source) {
+            Ok(code) => code,
+            Err(errors) => {
+                for error in errors.lines() {
+                    println!("Error: {}:{}", options.input, error);
+                }
+                return;
+            }
+        }
     } else if options.input.ends_with(".msa") {
         assemble(&source).expect("Failed to assemble code")
     } else {
@@ -186,6 +287,7 @@ fn main() {
         println!("Subcommands:");
         println!("  run <file> [options]");
         println!("  compile <file> [options]");
+        println!("  header");
         // Debugging
         // run(vec!["./examples/test.msa".to_string()]);
         return;
@@ -199,6 +301,10 @@ fn main() {
         "compile" => {
             compile(args[2..].to_vec());
         }
+        // C header native plugins are built with
+        "header" => {
+            print!("{}", ms_runtime::header());
+        }
         _ => {
             println!("Error: Invalid subcommand");
             return;
@@ -1,8 +1,16 @@
+use std::path::PathBuf;
+
 pub struct Options {
     pub output: String,
     pub input: String,
     pub entry: String,
     pub time: bool,
+    pub heap_limit: Option<usize>,
+    pub gc_threshold: Option<usize>,
+    pub gc_stats: bool,
+    pub overflow: ms_runtime::Overflow,
+    // Directories given with -module-path, searched before the default ones
+    pub module_paths: Vec<PathBuf>,
 }
 
 impl Options {
@@ -12,6 +20,11 @@ impl Options {
             input: String::new(),
             entry: "main.main".to_string(),
             time: false,
+            heap_limit: None,
+            gc_threshold: None,
+            gc_stats: false,
+            overflow: ms_runtime::Overflow::default(),
+            module_paths: vec![],
         }
     }
 }